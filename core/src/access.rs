@@ -0,0 +1,183 @@
+//! Memory the instruction at PC is about to read and write, worked out by decoding it.
+//!
+//! The CPU only reports writes to single addresses through its breakpoints, so anything else that
+//! watches memory traffic decodes the instruction before it runs. Pops and returns only read the
+//! stack, so they're left out. A step can service an interrupt instead of running the instruction,
+//! which [`interrupt`] picks up on afterwards.
+
+use gbc::{memory::Memory, Gbc, Mmu};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Access {
+    pub read: Option<u16>,
+    /// In the order they're made. Only pushes and `LD (nn),SP` write a second byte
+    pub writes: [Option<Write>; 2],
+}
+
+/// Where VBlank, STAT, timer, serial and joypad interrupts jump to
+const VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+const ZERO: u8 = 0x80;
+const CARRY: u8 = 0x10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Write {
+    pub addr: u16,
+    pub value: u8,
+}
+
+/// What the instruction at PC is going to access. Nothing while the CPU is halted, since it won't run
+pub fn next(emu: &Gbc<Mmu>) -> Access {
+    if emu.cpu.halted {
+        return Access::default();
+    }
+
+    let regs = &emu.cpu.regs;
+    let memory = &emu.cpu.memory;
+    let pc = regs.pc;
+    let byte = |offset: u16| memory.load(pc.wrapping_add(offset)).unwrap_or(0);
+    let bc = u16::from_be_bytes([regs.b, regs.c]);
+    let de = u16::from_be_bytes([regs.d, regs.e]);
+    let hl = u16::from_be_bytes([regs.h, regs.l]);
+    let write = |addr: u16, value: u8| [Some(Write { addr, value }), None];
+    let flags = regs.f.as_byte();
+
+    match byte(0) {
+        // LD (BC),A / LD (DE),A / LD (HL+),A / LD (HL-),A / LD (HL),A
+        0x02 => Access { read: None, writes: write(bc, regs.a) },
+        0x12 => Access { read: None, writes: write(de, regs.a) },
+        0x22 | 0x32 | 0x77 => Access { read: None, writes: write(hl, regs.a) },
+        // LD (HL),r
+        opcode @ 0x70..=0x75 => {
+            let value = [regs.b, regs.c, regs.d, regs.e, regs.h, regs.l][(opcode & 7) as usize];
+            Access { read: None, writes: write(hl, value) }
+        },
+        // LD (HL),n
+        0x36 => Access { read: None, writes: write(hl, byte(1)) },
+        // INC (HL) / DEC (HL)
+        opcode @ (0x34 | 0x35) => {
+            let old = memory.load(hl).unwrap_or(0);
+            let value = if opcode == 0x34 { old.wrapping_add(1) } else { old.wrapping_sub(1) };
+            Access { read: Some(hl), writes: write(hl, value) }
+        },
+        // LDH (n),A / LD (C),A / LD (nn),A
+        0xE0 => Access { read: None, writes: write(0xFF00 | byte(1) as u16, regs.a) },
+        0xE2 => Access { read: None, writes: write(0xFF00 | regs.c as u16, regs.a) },
+        0xEA => Access { read: None, writes: write(u16::from_le_bytes([byte(1), byte(2)]), regs.a) },
+        // LD A,(BC) / LD A,(DE) / LD A,(HL+) / LD A,(HL-)
+        0x0A => Access { read: Some(bc), writes: [None, None] },
+        0x1A => Access { read: Some(de), writes: [None, None] },
+        0x2A | 0x3A => Access { read: Some(hl), writes: [None, None] },
+        // LD r,(HL) and the ALU ops on (HL)
+        opcode @ (0x40..=0xBF) if opcode & 7 == 6 && opcode != 0x76 => Access { read: Some(hl), writes: [None, None] },
+        // LDH A,(n) / LD A,(C) / LD A,(nn)
+        0xF0 => Access { read: Some(0xFF00 | byte(1) as u16), writes: [None, None] },
+        0xF2 => Access { read: Some(0xFF00 | regs.c as u16), writes: [None, None] },
+        0xFA => Access { read: Some(u16::from_le_bytes([byte(1), byte(2)])), writes: [None, None] },
+        // everything on (HL) reads it, and all but BIT write it back
+        0xCB if byte(1) & 7 == 6 => {
+            let result = cb_result(byte(1), memory.load(hl).unwrap_or(0), flags & CARRY != 0);
+            Access { read: Some(hl), writes: result.map_or([None, None], |value| write(hl, value)) }
+        },
+        // LD (nn),SP
+        0x08 => {
+            let addr = u16::from_le_bytes([byte(1), byte(2)]);
+            let [low, high] = regs.sp.to_le_bytes();
+
+            Access {
+                read: None,
+                writes: [
+                    Some(Write { addr, value: low }),
+                    Some(Write { addr: addr.wrapping_add(1), value: high }),
+                ],
+            }
+        },
+        // PUSH BC / PUSH DE / PUSH HL / PUSH AF
+        0xC5 => push(regs.sp, bc),
+        0xD5 => push(regs.sp, de),
+        0xE5 => push(regs.sp, hl),
+        0xF5 => push(regs.sp, u16::from_be_bytes([regs.a, flags])),
+        // CALL nn, and CALL cc,nn when the condition holds
+        0xCD => push(regs.sp, pc.wrapping_add(3)),
+        opcode @ (0xC4 | 0xCC | 0xD4 | 0xDC) => {
+            let taken = match opcode {
+                0xC4 => flags & ZERO == 0,
+                0xCC => flags & ZERO != 0,
+                0xD4 => flags & CARRY == 0,
+                _ => flags & CARRY != 0,
+            };
+
+            if taken { push(regs.sp, pc.wrapping_add(3)) } else { Access::default() }
+        },
+        // RST
+        opcode if opcode & 0xC7 == 0xC7 => push(regs.sp, pc.wrapping_add(1)),
+        _ => Access::default(),
+    }
+}
+
+/// What a CB-prefixed `op` on (HL) writes back over `old`, or None for BIT, which only reads.
+/// The CPU reads the old value first too, so registers that don't read back as written get
+/// the same value the hardware would write.
+fn cb_result(op: u8, old: u8, carry: bool) -> Option<u8> {
+    let bit = op >> 3 & 7;
+
+    Some(match op >> 6 {
+        0 => match bit {
+            // RLC / RRC / RL / RR
+            0 => old.rotate_left(1),
+            1 => old.rotate_right(1),
+            2 => old << 1 | carry as u8,
+            3 => old >> 1 | (carry as u8) << 7,
+            // SLA / SRA / SWAP / SRL
+            4 => old << 1,
+            5 => old >> 1 | old & 0x80,
+            6 => old.rotate_left(4),
+            _ => old >> 1,
+        },
+        1 => return None,
+        2 => old & !(1 << bit),
+        _ => old | 1 << bit,
+    })
+}
+
+/// The push of PC an interrupt made, if the step that started with `pc` and `sp` serviced one
+/// instead of running the instruction there. That lands PC on a vector with the old PC pushed,
+/// where a CALL or RST to a vector would have pushed the address after it.
+pub fn interrupt(emu: &Gbc<Mmu>, (pc, sp): (u16, u16)) -> Option<Access> {
+    let regs = &emu.cpu.regs;
+    let memory = &emu.cpu.memory;
+    let pushed = u16::from_le_bytes([memory.load(regs.sp).unwrap_or(0), memory.load(regs.sp.wrapping_add(1)).unwrap_or(0)]);
+
+    let serviced = VECTORS.contains(&regs.pc) && regs.sp == sp.wrapping_sub(2) && pushed == pc;
+    serviced.then(|| push(sp, pc))
+}
+
+/// The high byte goes below SP, then the low byte below that
+fn push(sp: u16, value: u16) -> Access {
+    let [high, low] = value.to_be_bytes();
+
+    Access {
+        read: None,
+        writes: [
+            Some(Write { addr: sp.wrapping_sub(1), value: high }),
+            Some(Write { addr: sp.wrapping_sub(2), value: low }),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cb_results() {
+        // RLC, RR with carry, SRA, SWAP
+        assert_eq!(cb_result(0x06, 0x81, false), Some(0x03));
+        assert_eq!(cb_result(0x1E, 0x02, true), Some(0x81));
+        assert_eq!(cb_result(0x2E, 0x82, false), Some(0xC1));
+        assert_eq!(cb_result(0x36, 0x12, false), Some(0x21));
+        // BIT 7, RES 7, SET 0
+        assert_eq!(cb_result(0x7E, 0xFF, false), None);
+        assert_eq!(cb_result(0xBE, 0xFF, false), Some(0x7F));
+        assert_eq!(cb_result(0xC6, 0x80, false), Some(0x81));
+    }
+}
@@ -0,0 +1,634 @@
+//! Sound, played from the sound registers since the core doesn't produce any samples.
+//!
+//! The runner passes along every write to $FF10-$FF3F and how many cycles went by, and this runs
+//! the four channels, the frame sequencer and the mixer from those the way the hardware would.
+//! Writes land at the end of the instruction that made them, so timing is only as fine as that.
+
+use gbc::memory::Memory;
+
+use crate::pacing::CLOCK_SPEED;
+
+/// T-cycles per output sample
+const CYCLES_PER_SAMPLE: u32 = 64;
+/// Samples per second coming out of [`Apu::take_samples`]
+pub const SAMPLE_RATE: u32 = CLOCK_SPEED as u32 / CYCLES_PER_SAMPLE;
+/// The frame sequencer steps at 512 Hz
+const CYCLES_PER_SEQUENCER_STEP: u32 = 8192;
+
+const NR10: u16 = 0xFF10;
+const NR52: u16 = 0xFF26;
+const WAVE_RAM: u16 = 0xFF30;
+/// Sound registers that read back the way they were written: NR10, the envelopes, NR30, NR32,
+/// NR43, NR50 and NR51
+const READABLE: [u16; 9] = [NR10, 0xFF12, 0xFF17, 0xFF1A, 0xFF1C, 0xFF21, 0xFF22, 0xFF24, 0xFF25];
+
+const DUTIES: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0],
+];
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Envelope {
+    initial: u8,
+    up: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn write(&mut self, value: u8) {
+        self.initial = value >> 4;
+        self.up = value & 0x08 != 0;
+        self.period = value & 0x07;
+    }
+
+    fn trigger(&mut self) {
+        self.volume = self.initial;
+        self.timer = self.period;
+    }
+
+    fn step(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.period;
+
+            match self.up {
+                true if self.volume < 15 => self.volume += 1,
+                false if self.volume > 0 => self.volume -= 1,
+                _ => {},
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Length {
+    counter: u16,
+    enabled: bool,
+}
+
+impl Length {
+    /// Returns false once the channel should turn off
+    fn step(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter > 0;
+        }
+
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    enabled: bool,
+    shadow: u16,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Pulse {
+    on: bool,
+    dac: bool,
+    duty: u8,
+    position: u8,
+    frequency: u16,
+    timer: u16,
+    length: Length,
+    envelope: Envelope,
+    /// Only channel 1 has one
+    sweep: Option<Sweep>,
+}
+
+impl Pulse {
+    fn trigger(&mut self) {
+        self.on = self.dac;
+        if self.length.counter == 0 {
+            self.length.counter = 64;
+        }
+        self.timer = 2048 - self.frequency;
+        self.envelope.trigger();
+
+        if let Some(mut sweep) = self.sweep {
+            sweep.shadow = self.frequency;
+            sweep.timer = if sweep.period == 0 { 8 } else { sweep.period };
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+            self.sweep = Some(sweep);
+
+            if sweep.shift != 0 && self.sweep_target().is_none() {
+                self.on = false;
+            }
+        }
+    }
+
+    /// Where the sweep is taking the frequency next, or None if it overflows
+    fn sweep_target(&self) -> Option<u16> {
+        let sweep = self.sweep?;
+        let delta = sweep.shadow >> sweep.shift;
+        let target = if sweep.negate { sweep.shadow - delta } else { sweep.shadow + delta };
+
+        (target < 2048).then_some(target)
+    }
+
+    fn step_sweep(&mut self) {
+        let Some(mut sweep) = self.sweep.filter(|sweep| sweep.enabled) else {
+            return;
+        };
+
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            self.sweep = Some(sweep);
+            return;
+        }
+
+        sweep.timer = if sweep.period == 0 { 8 } else { sweep.period };
+        self.sweep = Some(sweep);
+
+        if sweep.period == 0 {
+            return;
+        }
+
+        match self.sweep_target() {
+            Some(target) if sweep.shift != 0 => {
+                self.frequency = target;
+                self.sweep = Some(Sweep { shadow: target, ..sweep });
+
+                if self.sweep_target().is_none() {
+                    self.on = false;
+                }
+            },
+            Some(_) => {},
+            None => self.on = false,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = 2048 - self.frequency;
+            self.position = (self.position + 1) & 7;
+        }
+    }
+
+    fn output(&self) -> Option<u8> {
+        self.dac.then(|| match self.on {
+            true => DUTIES[self.duty as usize][self.position as usize] * self.envelope.volume,
+            false => 0,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Wave {
+    on: bool,
+    dac: bool,
+    /// 0 is muted, then 100%, 50% and 25%
+    level: u8,
+    position: u8,
+    frequency: u16,
+    timer: u16,
+    length: Length,
+    ram: [u8; 16],
+}
+
+impl Wave {
+    fn trigger(&mut self) {
+        self.on = self.dac;
+        if self.length.counter == 0 {
+            self.length.counter = 256;
+        }
+        self.timer = 2048 - self.frequency;
+        self.position = 0;
+    }
+
+    /// The wave channel's timer runs twice as fast as the pulse channels'
+    fn tick(&mut self) {
+        for _ in 0..2 {
+            self.timer = self.timer.saturating_sub(1);
+            if self.timer == 0 {
+                self.timer = 2048 - self.frequency;
+                self.position = (self.position + 1) & 31;
+            }
+        }
+    }
+
+    fn output(&self) -> Option<u8> {
+        let byte = self.ram[self.position as usize / 2];
+        let sample = if self.position.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+
+        self.dac.then(|| match (self.on, self.level) {
+            (false, _) | (_, 0) => 0,
+            (true, level) => sample >> (level - 1),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Noise {
+    on: bool,
+    dac: bool,
+    shift: u8,
+    short: bool,
+    divisor: u8,
+    lfsr: u16,
+    timer: u32,
+    length: Length,
+    envelope: Envelope,
+}
+
+impl Noise {
+    /// In M-cycles
+    fn period(&self) -> u32 {
+        let divisor = if self.divisor == 0 { 2 } else { self.divisor as u32 * 4 };
+        divisor << self.shift
+    }
+
+    fn trigger(&mut self) {
+        self.on = self.dac;
+        if self.length.counter == 0 {
+            self.length.counter = 64;
+        }
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+        self.envelope.trigger();
+    }
+
+    fn tick(&mut self) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+
+        self.timer = self.period();
+        let bit = (self.lfsr ^ self.lfsr >> 1) & 1;
+        self.lfsr = self.lfsr >> 1 | bit << 14;
+
+        if self.short {
+            self.lfsr = self.lfsr & !(1 << 6) | bit << 6;
+        }
+    }
+
+    fn output(&self) -> Option<u8> {
+        let on = self.on && self.lfsr & 1 == 0;
+        self.dac.then_some(if on { self.envelope.volume } else { 0 })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Apu {
+    power: bool,
+    pulse1: Pulse,
+    pulse2: Pulse,
+    wave: Wave,
+    noise: Noise,
+    /// NR50
+    volume: u8,
+    /// NR51
+    panning: u8,
+    sequencer_step: u8,
+    sequencer_cycles: u32,
+    sample_cycles: u32,
+    /// What each side's high-pass filter has charged up to
+    capacitor: [f32; 2],
+    samples: Vec<[f32; 2]>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self {
+            power: false,
+            pulse1: Pulse { sweep: Some(Sweep::default()), ..Default::default() },
+            pulse2: Default::default(),
+            wave: Default::default(),
+            noise: Default::default(),
+            volume: 0,
+            panning: 0,
+            sequencer_step: 0,
+            sequencer_cycles: 0,
+            sample_cycles: 0,
+            capacitor: [0.0; 2],
+            samples: Vec::new(),
+        }
+    }
+}
+
+impl Apu {
+    /// Picks up the registers as they are in `memory`, for after the emu jumps somewhere else like
+    /// a save state. Channels stay quiet until the game next triggers them, since what they were
+    /// in the middle of isn't in memory.
+    ///
+    /// Only what reads back as it was written is picked up. Lengths and frequencies are write-only,
+    /// so they stay at 0 until the game writes them again rather than taking on the 1s they read as.
+    pub fn sync(&mut self, memory: &impl Memory) {
+        let samples = std::mem::take(&mut self.samples);
+        *self = Self { samples, ..Default::default() };
+
+        let load = |addr: u16| memory.load(addr).unwrap_or(0);
+        self.write(NR52, load(NR52));
+
+        for addr in READABLE.into_iter().chain(WAVE_RAM..WAVE_RAM + 16) {
+            self.write(addr, load(addr));
+        }
+
+        // duty and length enable share their registers with write-only bits, so they're set directly
+        self.pulse1.duty = load(0xFF11) >> 6;
+        self.pulse2.duty = load(0xFF16) >> 6;
+        self.pulse1.length.enabled = load(0xFF14) & 0x40 != 0;
+        self.pulse2.length.enabled = load(0xFF19) & 0x40 != 0;
+        self.wave.length.enabled = load(0xFF1E) & 0x40 != 0;
+        self.noise.length.enabled = load(0xFF23) & 0x40 != 0;
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        if !self.power && addr != NR52 && !(WAVE_RAM..WAVE_RAM + 16).contains(&addr) {
+            return;
+        }
+
+        let frequency_low = |frequency: u16| frequency & 0x700 | value as u16;
+        let frequency_high = |frequency: u16| frequency & 0xFF | (value as u16 & 0x07) << 8;
+
+        match addr {
+            0xFF10 => {
+                if let Some(ref mut sweep) = self.pulse1.sweep {
+                    sweep.period = value >> 4 & 0x07;
+                    sweep.negate = value & 0x08 != 0;
+                    sweep.shift = value & 0x07;
+                }
+            },
+            0xFF11 | 0xFF16 => {
+                let pulse = if addr == 0xFF11 { &mut self.pulse1 } else { &mut self.pulse2 };
+                pulse.duty = value >> 6;
+                pulse.length.counter = 64 - (value & 0x3F) as u16;
+            },
+            0xFF12 | 0xFF17 => {
+                let pulse = if addr == 0xFF12 { &mut self.pulse1 } else { &mut self.pulse2 };
+                pulse.envelope.write(value);
+                pulse.dac = value & 0xF8 != 0;
+                pulse.on &= pulse.dac;
+            },
+            0xFF13 | 0xFF18 => {
+                let pulse = if addr == 0xFF13 { &mut self.pulse1 } else { &mut self.pulse2 };
+                pulse.frequency = frequency_low(pulse.frequency);
+            },
+            0xFF14 | 0xFF19 => {
+                let pulse = if addr == 0xFF14 { &mut self.pulse1 } else { &mut self.pulse2 };
+                pulse.frequency = frequency_high(pulse.frequency);
+                pulse.length.enabled = value & 0x40 != 0;
+
+                if value & 0x80 != 0 {
+                    pulse.trigger();
+                }
+            },
+            0xFF1A => {
+                self.wave.dac = value & 0x80 != 0;
+                self.wave.on &= self.wave.dac;
+            },
+            0xFF1B => self.wave.length.counter = 256 - value as u16,
+            0xFF1C => self.wave.level = value >> 5 & 0x03,
+            0xFF1D => self.wave.frequency = frequency_low(self.wave.frequency),
+            0xFF1E => {
+                self.wave.frequency = frequency_high(self.wave.frequency);
+                self.wave.length.enabled = value & 0x40 != 0;
+
+                if value & 0x80 != 0 {
+                    self.wave.trigger();
+                }
+            },
+            0xFF20 => self.noise.length.counter = 64 - (value & 0x3F) as u16,
+            0xFF21 => {
+                self.noise.envelope.write(value);
+                self.noise.dac = value & 0xF8 != 0;
+                self.noise.on &= self.noise.dac;
+            },
+            0xFF22 => {
+                self.noise.shift = value >> 4;
+                self.noise.short = value & 0x08 != 0;
+                self.noise.divisor = value & 0x07;
+            },
+            0xFF23 => {
+                self.noise.length.enabled = value & 0x40 != 0;
+
+                if value & 0x80 != 0 {
+                    self.noise.trigger();
+                }
+            },
+            0xFF24 => self.volume = value,
+            0xFF25 => self.panning = value,
+            NR52 => {
+                let power = value & 0x80 != 0;
+
+                // turning sound off clears every register, but not wave RAM
+                if self.power && !power {
+                    let ram = self.wave.ram;
+                    let samples = std::mem::take(&mut self.samples);
+                    *self = Self { samples, capacitor: self.capacitor, ..Default::default() };
+                    self.wave.ram = ram;
+                }
+
+                self.power = power;
+            },
+            0xFF30..=0xFF3F => self.wave.ram[(addr - WAVE_RAM) as usize] = value,
+            _ => {},
+        }
+    }
+
+    /// Runs for `cycles` T-cycles, at normal speed
+    pub fn tick(&mut self, cycles: u64) {
+        // everything here steps in whole M-cycles
+        for _ in 0..cycles / 4 {
+            if self.power {
+                self.pulse1.tick();
+                self.pulse2.tick();
+                self.wave.tick();
+                self.noise.tick();
+
+                self.sequencer_cycles += 4;
+                if self.sequencer_cycles >= CYCLES_PER_SEQUENCER_STEP {
+                    self.sequencer_cycles -= CYCLES_PER_SEQUENCER_STEP;
+                    self.step_sequencer();
+                }
+            }
+
+            self.sample_cycles += 4;
+            if self.sample_cycles >= CYCLES_PER_SAMPLE {
+                self.sample_cycles -= CYCLES_PER_SAMPLE;
+                let sample = self.mix();
+                self.samples.push(sample);
+            }
+        }
+    }
+
+    /// Everything played since the last call, as stereo samples at [`SAMPLE_RATE`]
+    pub fn take_samples(&mut self) -> Vec<[f32; 2]> {
+        std::mem::take(&mut self.samples)
+    }
+
+    fn step_sequencer(&mut self) {
+        let step = self.sequencer_step;
+        self.sequencer_step = (step + 1) & 7;
+
+        if step.is_multiple_of(2) {
+            self.pulse1.on &= self.pulse1.length.step();
+            self.pulse2.on &= self.pulse2.length.step();
+            self.wave.on &= self.wave.length.step();
+            self.noise.on &= self.noise.length.step();
+        }
+
+        if step == 2 || step == 6 {
+            self.pulse1.step_sweep();
+        }
+
+        if step == 7 {
+            self.pulse1.envelope.step();
+            self.pulse2.envelope.step();
+            self.noise.envelope.step();
+        }
+    }
+
+    fn mix(&mut self) -> [f32; 2] {
+        let outputs = [self.pulse1.output(), self.pulse2.output(), self.wave.output(), self.noise.output()];
+        let mut mixed = [0.0; 2];
+
+        for (channel, output) in outputs.into_iter().enumerate() {
+            // a DAC that's off outputs nothing, and one that's on maps 0-15 onto 1.0 to -1.0
+            let Some(output) = output else {
+                continue;
+            };
+            let analog = 1.0 - output as f32 / 7.5;
+
+            if self.panning & (0x10 << channel) != 0 {
+                mixed[0] += analog;
+            }
+            if self.panning & (0x01 << channel) != 0 {
+                mixed[1] += analog;
+            }
+        }
+
+        let left_volume = (self.volume >> 4 & 0x07) as f32 + 1.0;
+        let right_volume = (self.volume & 0x07) as f32 + 1.0;
+        let volumes = [left_volume, right_volume];
+
+        // the hardware's high-pass filter, which takes out the DC offset from DACs that are on
+        let charge_factor = 0.999958f32.powi(CYCLES_PER_SAMPLE as i32);
+
+        std::array::from_fn(|side| {
+            let input = mixed[side] / 4.0 * volumes[side] / 8.0;
+            let out = input - self.capacitor[side];
+            self.capacitor[side] = input - out * charge_factor;
+            out
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gbc::Gbc;
+
+    use super::*;
+
+    fn playing_pulse() -> Apu {
+        let mut apu = Apu::default();
+        apu.write(NR52, 0x80);
+        apu.write(0xFF24, 0x77);
+        apu.write(0xFF25, 0x11);
+        apu.write(0xFF11, 0x80);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF13, 0x00);
+        apu.write(0xFF14, 0x87);
+        apu
+    }
+
+    #[test]
+    fn sample_rate() {
+        let mut apu = Apu::default();
+        apu.tick(CLOCK_SPEED as u64);
+        assert_eq!(apu.take_samples().len() as u32, SAMPLE_RATE);
+        assert!(apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn silent_when_off() {
+        let mut apu = Apu::default();
+        apu.tick(70224);
+        assert!(apu.take_samples().iter().all(|&sample| sample == [0.0, 0.0]));
+    }
+
+    #[test]
+    fn pulse_plays() {
+        let mut apu = playing_pulse();
+        apu.tick(70224);
+        assert!(apu.take_samples().iter().any(|sample| sample[0].abs() > 0.01));
+    }
+
+    #[test]
+    fn writes_ignored_while_off() {
+        let mut apu = Apu::default();
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF14, 0x80);
+
+        assert!(!apu.pulse1.on);
+    }
+
+    #[test]
+    fn length_stops_channel() {
+        let mut apu = playing_pulse();
+        // 63 of 64 used up, then enabling length
+        apu.write(0xFF11, 0x3F);
+        apu.write(0xFF14, 0x47);
+        apu.tick(CYCLES_PER_SEQUENCER_STEP as u64 * 2);
+
+        assert!(!apu.pulse1.on);
+    }
+
+    #[test]
+    fn power_off_keeps_wave_ram() {
+        let mut apu = playing_pulse();
+        apu.write(0xFF30, 0xAB);
+        apu.write(NR52, 0x00);
+
+        assert!(!apu.pulse1.on);
+        assert_eq!(apu.wave.ram[0], 0xAB);
+        assert_eq!(apu.panning, 0);
+    }
+
+    #[test]
+    fn sync_skips_write_only_bits() {
+        let mut memory = Gbc::new_flat(true, true).cpu.memory;
+        memory.set(NR52, 0xF1);
+        // duty 2, and a length and frequency that only read back as 1s
+        memory.set(0xFF11, 0xBF);
+        memory.set(0xFF13, 0xFF);
+        memory.set(0xFF14, 0xFF);
+
+        let mut apu = Apu::default();
+        apu.sync(&memory);
+
+        assert_eq!(apu.pulse1.duty, 2);
+        assert_eq!((apu.pulse1.length.counter, apu.pulse1.frequency), (0, 0));
+        assert!(apu.pulse1.length.enabled);
+        assert!(!apu.pulse1.on);
+    }
+
+    #[test]
+    fn noise_lfsr() {
+        let mut noise = Noise { lfsr: 0x7FFF, timer: 1, ..Default::default() };
+        noise.tick();
+        assert_eq!(noise.lfsr, 0x3FFF);
+
+        noise.lfsr = 0x0001;
+        noise.timer = 1;
+        noise.short = true;
+        noise.tick();
+        assert_eq!(noise.lfsr, 0x4040);
+    }
+}
@@ -0,0 +1,162 @@
+use std::{fs::File, io::{self, BufWriter, Seek, SeekFrom, Write}, path::Path};
+
+/// Where the runner sends resampled audio
+pub trait AudioSink: Send + 'static {
+    /// Rate that samples are resampled to before being pushed
+    fn sample_rate(&self) -> u32;
+
+    /// Stereo samples, in the range -1.0 to 1.0
+    fn push(&mut self, samples: &[[f32; 2]]);
+}
+
+/// Throws everything away, for when there's no audio device
+#[derive(Clone, Copy, Debug)]
+pub struct NullSink {
+    pub sample_rate: u32,
+}
+
+impl Default for NullSink {
+    fn default() -> Self {
+        Self { sample_rate: 48000 }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, _samples: &[[f32; 2]]) {}
+}
+
+/// Writes 16 bit stereo PCM to a .wav file, for headless runs and CI
+pub struct WavSink {
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    frames: u32,
+}
+
+impl WavSink {
+    const HEADER_LEN: u32 = 44;
+
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        let mut sink = Self {
+            writer: BufWriter::new(File::create(path)?),
+            sample_rate,
+            frames: 0,
+        };
+
+        sink.write_header()?;
+        Ok(sink)
+    }
+
+    /// The sizes in the header are only known once we're done, so it gets rewritten on every flush
+    fn write_header(&mut self) -> io::Result<()> {
+        let data_len = self.frames * 4;
+        let w = &mut self.writer;
+
+        w.seek(SeekFrom::Start(0))?;
+        w.write_all(b"RIFF")?;
+        w.write_all(&(Self::HEADER_LEN - 8 + data_len).to_le_bytes())?;
+        w.write_all(b"WAVEfmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        // PCM, 2 channels
+        w.write_all(&1u16.to_le_bytes())?;
+        w.write_all(&2u16.to_le_bytes())?;
+        w.write_all(&self.sample_rate.to_le_bytes())?;
+        w.write_all(&(self.sample_rate * 4).to_le_bytes())?;
+        // block align, bits per sample
+        w.write_all(&4u16.to_le_bytes())?;
+        w.write_all(&16u16.to_le_bytes())?;
+        w.write_all(b"data")?;
+        w.write_all(&data_len.to_le_bytes())?;
+        w.seek(SeekFrom::End(0))?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_header()?;
+        self.writer.flush()
+    }
+}
+
+impl AudioSink for WavSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[[f32; 2]]) {
+        for sample in samples {
+            for channel in sample {
+                let value = (channel.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                if self.writer.write_all(&value.to_le_bytes()).is_err() {
+                    return;
+                }
+            }
+
+            self.frames += 1;
+        }
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Linear interpolating resampler that carries its position across calls
+#[derive(Clone, Copy, Debug)]
+pub struct Resampler {
+    /// Input samples per output sample at normal speed
+    ratio: f64,
+    /// Input samples per output sample
+    step: f64,
+    /// Position in the input, where 0.0 is `prev`
+    pos: f64,
+    prev: [f32; 2],
+}
+
+impl Resampler {
+    pub fn new(from: u32, to: u32) -> Self {
+        Self {
+            ratio: from as f64 / to as f64,
+            step: from as f64 / to as f64,
+            pos: 0.0,
+            prev: [0.0; 2],
+        }
+    }
+
+    /// Takes in `speed` times as many samples per output sample, for input that's being made
+    /// faster or slower than real time
+    pub fn set_speed(&mut self, speed: f64) {
+        self.step = self.ratio * speed;
+    }
+
+    pub fn process(&mut self, input: &[[f32; 2]], output: &mut Vec<[f32; 2]>) {
+        let Some(&last) = input.last() else {
+            return;
+        };
+
+        // `prev` sits in front of the input, so there are input.len() gaps to interpolate over
+        let get = |i: usize| if i == 0 { self.prev } else { input[i - 1] };
+        let end = input.len() as f64;
+
+        while self.pos < end {
+            let i = self.pos as usize;
+            let frac = (self.pos - i as f64) as f32;
+            let (a, b) = (get(i), get(i + 1));
+
+            output.push([
+                a[0] + (b[0] - a[0]) * frac,
+                a[1] + (b[1] - a[1]) * frac,
+            ]);
+
+            self.pos += self.step;
+        }
+
+        self.pos -= end;
+        self.prev = last;
+    }
+}
@@ -0,0 +1,61 @@
+use std::{path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use gbc::{Gbc, Mmu};
+
+use crate::mbc::{Clock, Mapper};
+
+/// How often (in frames) the runner checks whether cartridge RAM needs flushing
+pub const FLUSH_INTERVAL: usize = 60 * 5;
+
+/// The RTC footer used by VBA-M, BGB, mGBA and friends:
+/// current S/M/H/DL/DH and latched S/M/H/DL/DH as LE u32s, then a LE unix timestamp.
+/// Some emulators write a 32 bit timestamp instead, so both are accepted on load.
+const RTC_FOOTER_LEN: usize = 48;
+const RTC_FOOTER_LEN_SHORT: usize = 44;
+
+/// Battery saves live next to the rom, as `<rom>.sav`
+pub fn path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("sav")
+}
+
+/// External RAM followed by the RTC footer if the cartridge has a clock, as tracked by `mapper`
+pub fn encode(emu: &mut Gbc<Mmu>, mapper: &Mapper) -> Vec<u8> {
+    let mut out = mapper.read_ram(&mut emu.cpu.memory);
+
+    if let Some(clock) = mapper.clock() {
+        for reg in clock.current.iter().chain(&clock.latched) {
+            out.extend_from_slice(&(*reg as u32).to_le_bytes());
+        }
+
+        out.extend_from_slice(&unix_time().to_le_bytes());
+    }
+
+    out
+}
+
+pub fn decode(emu: &mut Gbc<Mmu>, mapper: &mut Mapper, data: &[u8]) {
+    let (ram, footer) = data.split_at(mapper.ram_len.min(data.len()));
+
+    mapper.write_ram(&mut emu.cpu.memory, ram);
+
+    if !mapper.rtc {
+        return;
+    }
+
+    let timestamp = match footer.len() {
+        RTC_FOOTER_LEN => u64::from_le_bytes(footer[40..48].try_into().unwrap()),
+        RTC_FOOTER_LEN_SHORT => u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64,
+        _ => return,
+    };
+
+    let reg = |i: usize| footer[i * 4];
+    let mut clock = Clock::new([reg(0), reg(1), reg(2), reg(3), reg(4)], [reg(5), reg(6), reg(7), reg(8), reg(9)]);
+
+    // the clock kept ticking while the game was closed
+    clock.advance(unix_time().saturating_sub(timestamp));
+    mapper.write_clock_registers(&mut emu.cpu.memory, clock);
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,277 @@
+//! The user's list of breakpoints, saved next to the rom as `<rom>.bpt`.
+//!
+//! Every entry has a trigger written as `<kind> <arg>`, which is also how they're saved:
+//! ```text
+//! pc 0150
+//! read C000          read FE00-FE9F
+//! write C000         write FE00-FE9F         write C0A0=3F/F0
+//! reg a              flag z
+//! if 3 ly == 144 && a > 3
+//! ```
+
+use std::{fmt::Display, path::{Path, PathBuf}, str::FromStr};
+
+use crate::{comms::EmuMsgIn, expr::Expr, lines, runner::{Breakpoint, Condition}};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    Cpu(Breakpoint),
+    Condition {
+        expr: Expr,
+        /// Kept around for display, since the expression doesn't remember how it was written
+        source: String,
+        /// How many times it has to become true before breaking
+        hit_count: u32,
+    },
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let breakpoint = match self {
+            Self::Cpu(breakpoint) => breakpoint,
+            Self::Condition { source, hit_count, .. } => return write!(f, "if {hit_count} {source}"),
+        };
+
+        match breakpoint {
+            Breakpoint::A => write!(f, "reg a"),
+            Breakpoint::B => write!(f, "reg b"),
+            Breakpoint::C => write!(f, "reg c"),
+            Breakpoint::D => write!(f, "reg d"),
+            Breakpoint::H => write!(f, "reg h"),
+            Breakpoint::L => write!(f, "reg l"),
+            Breakpoint::Zero => write!(f, "flag z"),
+            Breakpoint::Subtract => write!(f, "flag n"),
+            Breakpoint::HalfCarry => write!(f, "flag h"),
+            Breakpoint::Carry => write!(f, "flag c"),
+            Breakpoint::MemoryWrite(addr) => write!(f, "write {addr:04X}"),
+            Breakpoint::MemoryRead(addr) => write!(f, "read {addr:04X}"),
+            Breakpoint::ReadRange(start, end) => write!(f, "read {start:04X}-{end:04X}"),
+            Breakpoint::WriteRange(start, end) => write!(f, "write {start:04X}-{end:04X}"),
+            Breakpoint::WriteValue { addr, value, mask } => write!(f, "write {addr:04X}={value:02X}/{mask:02X}"),
+            Breakpoint::Pc(addr) => write!(f, "pc {addr:04X}"),
+        }
+    }
+}
+
+impl FromStr for Trigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+        let arg = arg.trim();
+        let bad = || format!("bad {kind} breakpoint '{arg}'");
+
+        let breakpoint = match kind {
+            "pc" => Breakpoint::Pc(parse_addr(arg).ok_or_else(bad)?),
+            "read" => match arg.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = parse_range(start, end).ok_or_else(bad)?;
+                    Breakpoint::ReadRange(start, end)
+                },
+                None => Breakpoint::MemoryRead(parse_addr(arg).ok_or_else(bad)?),
+            },
+            "write" => if let Some((start, end)) = arg.split_once('-') {
+                let (start, end) = parse_range(start, end).ok_or_else(bad)?;
+                Breakpoint::WriteRange(start, end)
+            } else if let Some((addr, rest)) = arg.split_once('=') {
+                let (value, mask) = rest.split_once('/').unwrap_or((rest, "FF"));
+
+                Breakpoint::WriteValue {
+                    addr: parse_addr(addr).ok_or_else(bad)?,
+                    value: u8::from_str_radix(value.trim(), 16).map_err(|_| bad())?,
+                    mask: u8::from_str_radix(mask.trim(), 16).map_err(|_| bad())?,
+                }
+            } else {
+                Breakpoint::MemoryWrite(parse_addr(arg).ok_or_else(bad)?)
+            },
+            "reg" => match arg.to_ascii_lowercase().as_str() {
+                "a" => Breakpoint::A,
+                "b" => Breakpoint::B,
+                "c" => Breakpoint::C,
+                "d" => Breakpoint::D,
+                "h" => Breakpoint::H,
+                "l" => Breakpoint::L,
+                _ => return Err(bad()),
+            },
+            "flag" => match arg.to_ascii_lowercase().as_str() {
+                "z" => Breakpoint::Zero,
+                "n" => Breakpoint::Subtract,
+                "h" => Breakpoint::HalfCarry,
+                "c" => Breakpoint::Carry,
+                _ => return Err(bad()),
+            },
+            "if" => {
+                let (hit_count, source) = arg.split_once(' ').ok_or_else(bad)?;
+                let hit_count = hit_count.parse().map_err(|_| bad())?;
+                let source = source.trim().to_owned();
+                let expr = Expr::parse(&source).map_err(|err| err.to_string())?;
+
+                return Ok(Self::Condition { expr, source, hit_count });
+            },
+            _ => return Err(format!("unknown breakpoint kind '{kind}'")),
+        };
+
+        Ok(Self::Cpu(breakpoint))
+    }
+}
+
+fn parse_addr(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.trim().trim_start_matches('$'), 16).ok()
+}
+
+/// Inclusive, and backwards ranges are refused
+fn parse_range(start: &str, end: &str) -> Option<(u16, u16)> {
+    let (start, end) = (parse_addr(start)?, parse_addr(end)?);
+    (start <= end).then_some((start, end))
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub id: usize,
+    pub label: String,
+    pub enabled: bool,
+    pub trigger: Trigger,
+    /// Times this has stopped the emu this session
+    pub hits: u32,
+}
+
+impl Entry {
+    /// What to send the runner to set this breakpoint
+    pub fn set_msg(&self) -> EmuMsgIn {
+        match self.trigger {
+            Trigger::Cpu(breakpoint) => EmuMsgIn::SetBreakpoint(breakpoint),
+            Trigger::Condition { ref expr, hit_count, .. } => EmuMsgIn::SetCondition(Condition {
+                id: self.id,
+                expr: expr.clone(),
+                hit_count,
+            }),
+        }
+    }
+
+    /// What to send the runner to unset this breakpoint
+    pub fn unset_msg(&self) -> EmuMsgIn {
+        match self.trigger {
+            Trigger::Cpu(breakpoint) => EmuMsgIn::UnsetBreakpoint(breakpoint),
+            Trigger::Condition { .. } => EmuMsgIn::UnsetCondition(self.id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BreakpointList {
+    pub entries: Vec<Entry>,
+    next_id: usize,
+}
+
+impl BreakpointList {
+    /// Adds an enabled breakpoint, returning None if the same trigger is already in the list
+    pub fn add(&mut self, label: String, trigger: Trigger) -> Option<&mut Entry> {
+        // the CPU only has one of each breakpoint, so disabling a duplicate would disable both
+        if self.entries.iter().any(|entry| entry.trigger == trigger) {
+            return None;
+        }
+
+        self.entries.push(Entry {
+            id: self.next_id,
+            label,
+            enabled: true,
+            trigger,
+            hits: 0,
+        });
+        self.next_id += 1;
+
+        self.entries.last_mut()
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Entry> {
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        Some(self.entries.remove(index))
+    }
+
+    /// Counts a hit on whichever enabled entry set `breakpoint`
+    pub fn hit(&mut self, breakpoint: Breakpoint) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.enabled && entry.trigger == Trigger::Cpu(breakpoint)) {
+            entry.hits += 1;
+        }
+    }
+
+    pub fn condition_hit(&mut self, id: usize) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id) {
+            entry.hits += 1;
+        }
+    }
+
+    /// Messages that set every enabled breakpoint on a fresh runner
+    pub fn set_msgs(&self) -> impl Iterator<Item = EmuMsgIn> + '_ {
+        self.entries.iter().filter(|entry| entry.enabled).map(Entry::set_msg)
+    }
+}
+
+/// Breakpoints live next to the rom, as `<rom>.bpt`
+pub fn path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("bpt")
+}
+
+/// One breakpoint per line, as `<enabled 0/1>\t<label>\t<trigger>`
+pub fn encode(list: &BreakpointList) -> String {
+    let mut out = String::new();
+
+    for entry in &list.entries {
+        lines::encode(&mut out, entry.enabled, &entry.label, &entry.trigger);
+    }
+
+    out
+}
+
+/// Lines that don't parse are reported and skipped, so one bad line doesn't lose the rest
+pub fn decode(text: &str) -> (BreakpointList, Vec<String>) {
+    let mut list = BreakpointList::default();
+
+    let errors = lines::decode(text, |enabled, label, trigger| {
+        if let Some(entry) = list.add(label.to_owned(), trigger.parse()?) {
+            entry.enabled = enabled;
+        }
+
+        Ok(())
+    });
+
+    (list, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(text: &str) -> Trigger {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn trigger_round_trip() {
+        for text in [
+            "pc 0150",
+            "read C000",
+            "read FE00-FE9F",
+            "write C000",
+            "write FE00-FE9F",
+            "write C0A0=3F/F0",
+            "reg a",
+            "flag z",
+            "if 3 ly == 144 && a > 3",
+        ] {
+            assert_eq!(trigger(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn trigger_forms() {
+        assert_eq!(trigger("pc $150"), Trigger::Cpu(Breakpoint::Pc(0x150)));
+        assert_eq!(trigger("write C0A0=3F"), Trigger::Cpu(Breakpoint::WriteValue { addr: 0xC0A0, value: 0x3F, mask: 0xFF }));
+        assert_eq!(trigger("reg H"), Trigger::Cpu(Breakpoint::H));
+
+        assert!("read FE9F-FE00".parse::<Trigger>().is_err());
+        assert!("reg f".parse::<Trigger>().is_err());
+        assert!("if x ly == 144".parse::<Trigger>().is_err());
+        assert!("jump 0150".parse::<Trigger>().is_err());
+    }
+}
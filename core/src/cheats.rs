@@ -0,0 +1,234 @@
+//! Game Genie and GameShark codes, saved next to the rom as `<rom>.cht`.
+//!
+//! A cheat can hold several codes separated by spaces, since plenty of them come in pairs:
+//! ```text
+//! 00A-17B-C49          Game Genie, ROM $4A17 = $00 if it was $C8
+//! 3E8-1AF              Game Genie without a compare
+//! 0163C2D0             GameShark, $D0C2 = $63 every frame
+//! ```
+//!
+//! Cheats belong to the session rather than to save states, so loading a state or rewinding
+//! keeps whichever cheats are enabled right now.
+
+use std::{fmt::Display, path::{Path, PathBuf}, str::FromStr};
+
+use crate::lines;
+
+/// Size of a ROM bank, and of the banked area at $4000-$7FFF
+const ROM_BANK_LEN: usize = 0x4000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Code {
+    /// Replaces what the CPU reads from a ROM address, but only while the ROM holds `compare` there,
+    /// so banked code that happens to share the address is left alone. Codes for $4000-$7FFF need one,
+    /// since nothing else says which bank they're for
+    GameGenie { addr: u16, value: u8, compare: Option<u8> },
+    /// Writes a RAM byte every frame. For $D000-$DFFF on CGB and for cartridge RAM, `bank` is the bank
+    /// written to, whichever one the game has mapped in
+    GameShark { bank: u8, addr: u16, value: u8 },
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::GameGenie { addr, value, compare: None } => write!(f, "ROM ${addr:04X} = ${value:02X}"),
+            Self::GameGenie { addr, value, compare: Some(compare) } => write!(f, "ROM ${addr:04X} = ${value:02X} if ${compare:02X}"),
+            Self::GameShark { addr, value, .. } => write!(f, "RAM ${addr:04X} = ${value:02X}"),
+        }
+    }
+}
+
+impl FromStr for Code {
+    type Err = String;
+
+    /// `ABC-DEF` or `ABC-DEF-GHI` for Game Genie, `ABCDEFGH` for GameShark
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("bad code '{s}'");
+        let digits = s.trim().chars()
+            .filter(|&c| c != '-')
+            .map(|c| c.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(bad)?;
+
+        match (digits.len(), s.contains('-')) {
+            (6 | 9, true) => {
+                let value = digits[0] << 4 | digits[1];
+                // the top nibble is stored inverted, after the rest of the address
+                let addr = ((digits[5] ^ 0xF) as u16) << 12 | (digits[2] as u16) << 8 | (digits[3] as u16) << 4 | digits[4] as u16;
+                // the middle digit of the last group is only there to make the code harder to guess
+                let compare = (digits.len() == 9).then(|| (digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA);
+
+                if addr >= 0x8000 {
+                    return Err(format!("Game Genie code '{s}' isn't in ROM"));
+                }
+
+                if addr >= ROM_BANK_LEN as u16 && compare.is_none() {
+                    return Err(format!("Game Genie code '{s}' is for banked ROM, so it needs a compare"));
+                }
+
+                Ok(Self::GameGenie { addr, value, compare })
+            },
+            (8, false) => {
+                let byte = |i: usize| digits[i] << 4 | digits[i + 1];
+
+                Ok(Self::GameShark {
+                    bank: byte(0),
+                    value: byte(2),
+                    addr: u16::from_le_bytes([byte(4), byte(6)]),
+                })
+            },
+            _ => Err(bad()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub label: String,
+    pub enabled: bool,
+    /// The codes as typed, which is what gets saved
+    pub text: String,
+    pub codes: Vec<Code>,
+}
+
+impl Cheat {
+    pub fn new(label: String, text: &str) -> Result<Self, String> {
+        let codes = text.split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if codes.is_empty() {
+            return Err("no codes given".to_owned());
+        }
+
+        Ok(Self {
+            label,
+            enabled: true,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" ").to_ascii_uppercase(),
+            codes,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CheatList {
+    pub entries: Vec<Cheat>,
+}
+
+impl CheatList {
+    /// Codes of every enabled cheat, for `EmuMsgIn::SetCheats`
+    pub fn active(&self) -> Vec<Code> {
+        self.entries.iter()
+            .filter(|cheat| cheat.enabled)
+            .flat_map(|cheat| cheat.codes.iter().copied())
+            .collect()
+    }
+}
+
+/// A copy of `rom` with every Game Genie code in `codes` written into it.
+/// The core can't intercept reads from ROM, so the copy gets loaded in place of the real thing.
+/// A code for $4000-$7FFF goes into every bank holding its compare, since the Game Genie patches whichever
+/// one is mapped in. Without a compare there's no telling which bank it's for, so it's left out
+pub fn patch_rom(rom: &[u8], codes: &[Code]) -> Vec<u8> {
+    let mut patched = rom.to_vec();
+
+    for code in codes {
+        let Code::GameGenie { addr, value, compare } = *code else {
+            continue;
+        };
+
+        // bank 0 is always at $0000-$3FFF
+        let offsets = match (addr as usize, compare) {
+            (addr @ ..ROM_BANK_LEN, _) => addr..addr + 1,
+            (_, None) => continue,
+            (addr, Some(_)) => addr..patched.len(),
+        };
+
+        for offset in offsets.step_by(ROM_BANK_LEN) {
+            let Some(byte) = patched.get_mut(offset) else {
+                break;
+            };
+
+            if compare.is_none_or(|compare| *byte == compare) {
+                *byte = value;
+            }
+        }
+    }
+
+    patched
+}
+
+/// Cheats live next to the rom, as `<rom>.cht`
+pub fn path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("cht")
+}
+
+/// One cheat per line, as `<enabled 0/1>\t<label>\t<codes>`
+pub fn encode(list: &CheatList) -> String {
+    let mut out = String::new();
+
+    for cheat in &list.entries {
+        lines::encode(&mut out, cheat.enabled, &cheat.label, &cheat.text);
+    }
+
+    out
+}
+
+/// Lines that don't parse are reported and skipped, so one bad line doesn't lose the rest
+pub fn decode(text: &str) -> (CheatList, Vec<String>) {
+    let mut list = CheatList::default();
+
+    let errors = lines::decode(text, |enabled, label, codes| {
+        list.entries.push(Cheat { enabled, ..Cheat::new(label.to_owned(), codes)? });
+        Ok(())
+    });
+
+    (list, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_genie() {
+        assert_eq!("00A-17B-C49".parse(), Ok(Code::GameGenie { addr: 0x4A17, value: 0x00, compare: Some(0xC8) }));
+        assert_eq!("3e8-1af".parse(), Ok(Code::GameGenie { addr: 0x081A, value: 0x3E, compare: None }));
+        // the inverted top nibble puts this at $8A17
+        assert!("00A-177".parse::<Code>().is_err());
+        assert!("00A-17B-C4".parse::<Code>().is_err());
+        // banked, without a compare
+        assert!("00A-17B".parse::<Code>().is_err());
+    }
+
+    #[test]
+    fn game_shark() {
+        assert_eq!("0163C2D0".parse(), Ok(Code::GameShark { bank: 0x01, addr: 0xD0C2, value: 0x63 }));
+        assert!("0163C2D".parse::<Code>().is_err());
+        assert!("0163C2DG".parse::<Code>().is_err());
+    }
+
+    #[test]
+    fn patch() {
+        let mut rom = vec![0; ROM_BANK_LEN * 4];
+        rom[0x0A17] = 0x11;
+        rom[0x4A17] = 0xC8;
+        rom[0xCA17] = 0xC8;
+
+        let codes = [
+            Code::GameGenie { addr: 0x0A17, value: 0x22, compare: None },
+            Code::GameGenie { addr: 0x4A17, value: 0x00, compare: Some(0xC8) },
+            Code::GameGenie { addr: 0x7FFF, value: 0x33, compare: None },
+            Code::GameShark { bank: 0, addr: 0x0A17, value: 0x44 },
+        ];
+        let patched = patch_rom(&rom, &codes);
+
+        assert_eq!(patched[0x0A17], 0x22);
+        // only the banks holding the compare value
+        assert_eq!((patched[0x4A17], patched[0x8A17], patched[0xCA17]), (0x00, 0x00, 0x00));
+        assert_eq!(rom[0x8A17], 0);
+        // banked without a compare, so there's no knowing where it goes
+        assert_eq!((patched[0x7FFF], patched[0xBFFF], patched[0xFFFF]), (0, 0, 0));
+        assert_eq!(patched[0x3FFF], 0);
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use crate::{cheats::Code, events::Event, movie::{MovieStart, MovieStatus}, runner::{Breakpoint, Condition}, state::StateDump, trace::TraceFilter};
+
+#[derive(Clone, Debug)]
+pub enum EmuMsgIn {
+    LoadRom,
+    Exit,
+    Pause,
+    Resume,
+    Step(usize),
+    /// Runs until the next frame is drawn, then pauses
+    FrameAdvance,
+    /// Runs through a CALL or RST, or steps a single instruction otherwise
+    StepOver,
+    /// Runs until the current routine returns
+    StepOut,
+    RunTo(u16),
+    SetBreakpoint(Breakpoint),
+    UnsetBreakpoint(Breakpoint),
+    /// Adds a conditional breakpoint, or replaces the one with the same id
+    SetCondition(Condition),
+    UnsetCondition(usize),
+    /// Speed multiplier, clamped to 0.25-8.0
+    SetSpeed(f64),
+    /// Uncapped speed while held
+    Turbo(bool),
+    SlowMotion(bool),
+    /// 0.0-1.0
+    SetVolume(f32),
+    SetMuted(bool),
+    ButtonPressed(gbc::Button),
+    ButtonReleased(gbc::Button),
+    SaveState(usize),
+    LoadState(usize),
+    RewindStart,
+    RewindStop,
+    MovieRecord(MovieStart),
+    /// Plays back the movie saved next to the rom
+    MoviePlay,
+    MovieStop,
+    MovieReadOnly(bool),
+    /// Writes a trace in Gameboy Doctor's format to `path`, replacing any trace already running.
+    /// `pin_ly` holds LY at $90 to line up with Gameboy Doctor's logs
+    TraceStart { path: PathBuf, filter: TraceFilter, pin_ly: bool },
+    TraceStop,
+    /// Writes through the MMU like the CPU would, so writes to ROM go to the MBC
+    WriteMemory { addr: u16, bytes: Vec<u8> },
+    /// Replaces every register, meant for while the emu is stopped
+    SetRegisters(gbc::Registers),
+    /// Writes `bytes` to `addr` every frame, replacing any freeze already there
+    Freeze { addr: u16, bytes: Vec<u8> },
+    Unfreeze(u16),
+    /// Replaces every active cheat code
+    SetCheats(Vec<Code>),
+    /// Turns the event log on or off
+    LogEvents(bool),
+}
+
+#[derive(Clone, Debug)]
+pub enum EmuMsgOut {
+    State(StateDump),
+    /// Sent once the runner has finished cleaning up after `EmuMsgIn::Exit`
+    Exited,
+    /// Sent whenever the active movie changes, None if there isn't one
+    Movie(Option<MovieStatus>),
+    /// One of the user's breakpoints stopped the emu
+    BreakpointHit(Breakpoint),
+    /// A condition stopped the emu, by id
+    ConditionHit(usize),
+    /// Events logged since the last batch, sent every frame and whenever the emu stops
+    Events(Vec<Event>),
+    /// Something the user should know about, like a state being saved
+    Status(String),
+    /// Something that went wrong, like a file that couldn't be read or written
+    Error(String),
+}
\ No newline at end of file
@@ -0,0 +1,241 @@
+//! SM83 disassembler, working on a flat copy of the address space like `StateDump::memory`
+
+const R: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const RP: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const RP2: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const CC: [&str; 4] = ["NZ", "Z", "NC", "C"];
+const ALU: [&str; 8] = ["ADD A,", "ADC A,", "SUB ", "SBC A,", "AND ", "XOR ", "OR ", "CP "];
+const ROT: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: u16,
+    pub len: u8,
+    pub text: String,
+    /// Where a jump, call or RST goes, if that's known without running it
+    pub target: Option<u16>,
+}
+
+/// Length in bytes of the instruction starting with `opcode`
+pub fn len(opcode: u8) -> u8 {
+    match opcode {
+        0xCB => 2,
+        // LD rr,n16 / LD (a16),SP
+        0x01 | 0x11 | 0x21 | 0x31 | 0x08 => 3,
+        // JP / CALL, conditional or not, and LD to or from (a16)
+        0xC2 | 0xC3 | 0xCA | 0xD2 | 0xDA
+        | 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC
+        | 0xEA | 0xFA => 3,
+        // LD r,n8
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => 2,
+        // STOP and the JRs
+        0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => 2,
+        // LDH, ADD SP,e8, LD HL,SP+e8
+        0xE0 | 0xF0 | 0xE8 | 0xF8 => 2,
+        // ALU ops on n8
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => 2,
+        _ => 1,
+    }
+}
+
+pub fn decode(memory: &[u8], addr: u16) -> Instruction {
+    let byte = |offset: u16| memory.get(addr.wrapping_add(offset) as usize).copied().unwrap_or(0);
+    let opcode = byte(0);
+    let n8 = byte(1);
+    let n16 = u16::from_le_bytes([byte(1), byte(2)]);
+    let e8 = n8 as i8;
+    let relative = addr.wrapping_add(2).wrapping_add_signed(e8 as i16);
+
+    let (x, y, z) = (opcode >> 6, (opcode >> 3 & 7) as usize, (opcode & 7) as usize);
+    let (p, q) = (y >> 1, y & 1);
+    let mut target = None;
+
+    let text = match (x, z) {
+        (0, 0) => match y {
+            0 => "NOP".to_owned(),
+            1 => format!("LD (${n16:04X}),SP"),
+            2 => "STOP".to_owned(),
+            3 => {
+                target = Some(relative);
+                format!("JR ${relative:04X}")
+            },
+            _ => {
+                target = Some(relative);
+                format!("JR {},${relative:04X}", CC[y - 4])
+            },
+        },
+        (0, 1) if q == 0 => format!("LD {},${n16:04X}", RP[p]),
+        (0, 1) => format!("ADD HL,{}", RP[p]),
+        (0, 2) => {
+            let mem = ["(BC)", "(DE)", "(HL+)", "(HL-)"][p];
+
+            if q == 0 {
+                format!("LD {mem},A")
+            } else {
+                format!("LD A,{mem}")
+            }
+        },
+        (0, 3) if q == 0 => format!("INC {}", RP[p]),
+        (0, 3) => format!("DEC {}", RP[p]),
+        (0, 4) => format!("INC {}", R[y]),
+        (0, 5) => format!("DEC {}", R[y]),
+        (0, 6) => format!("LD {},${n8:02X}", R[y]),
+        (0, _) => ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"][y].to_owned(),
+        (1, 6) if y == 6 => "HALT".to_owned(),
+        (1, _) => format!("LD {},{}", R[y], R[z]),
+        (2, _) => format!("{}{}", ALU[y], R[z]),
+        (_, 0) => match y {
+            0..=3 => format!("RET {}", CC[y]),
+            4 => format!("LDH ($FF{n8:02X}),A"),
+            5 => format!("ADD SP,{e8}"),
+            6 => format!("LDH A,($FF{n8:02X})"),
+            _ => format!("LD HL,SP{e8:+}"),
+        },
+        (_, 1) if q == 0 => format!("POP {}", RP2[p]),
+        (_, 1) => ["RET", "RETI", "JP HL", "LD SP,HL"][p].to_owned(),
+        (_, 2) => match y {
+            0..=3 => {
+                target = Some(n16);
+                format!("JP {},${n16:04X}", CC[y])
+            },
+            4 => "LD ($FF00+C),A".to_owned(),
+            5 => format!("LD (${n16:04X}),A"),
+            6 => "LD A,($FF00+C)".to_owned(),
+            _ => format!("LD A,(${n16:04X})"),
+        },
+        (_, 3) => match y {
+            0 => {
+                target = Some(n16);
+                format!("JP ${n16:04X}")
+            },
+            1 => {
+                let (x, y, z) = (n8 >> 6, n8 >> 3 & 7, (n8 & 7) as usize);
+
+                match x {
+                    0 => format!("{} {}", ROT[y as usize], R[z]),
+                    1 => format!("BIT {y},{}", R[z]),
+                    2 => format!("RES {y},{}", R[z]),
+                    _ => format!("SET {y},{}", R[z]),
+                }
+            },
+            6 => "DI".to_owned(),
+            7 => "EI".to_owned(),
+            _ => format!("DB ${opcode:02X}"),
+        },
+        (_, 4) if y < 4 => {
+            target = Some(n16);
+            format!("CALL {},${n16:04X}", CC[y])
+        },
+        (_, 5) if q == 0 => format!("PUSH {}", RP2[p]),
+        (_, 5) if p == 0 => {
+            target = Some(n16);
+            format!("CALL ${n16:04X}")
+        },
+        (_, 6) => format!("{}${n8:02X}", ALU[y]),
+        (_, 7) => {
+            let vector = y as u16 * 8;
+            target = Some(vector);
+            format!("RST ${vector:02X}")
+        },
+        // the handful of opcodes that don't exist
+        _ => format!("DB ${opcode:02X}"),
+    };
+
+    Instruction {
+        addr,
+        len: len(opcode),
+        text,
+        target,
+    }
+}
+
+/// Start addresses of every instruction in a linear sweep of the address space.
+/// The sweep gets pulled back in line at each of `anchors`, so instructions there
+/// always start a line even when data before them throws the sweep off.
+pub fn line_starts(memory: &[u8], anchors: &[u16]) -> Vec<u16> {
+    let mut starts = Vec::with_capacity(0x8000);
+    let mut addr: u32 = 0;
+
+    while addr <= u16::MAX as u32 {
+        starts.push(addr as u16);
+
+        let opcode = memory.get(addr as usize).copied().unwrap_or(0);
+        let next = addr + len(opcode) as u32;
+
+        // an anchor inside this instruction means it's not really an instruction
+        addr = anchors.iter()
+            .map(|&anchor| anchor as u32)
+            .filter(|&anchor| anchor > addr && anchor < next)
+            .min()
+            .unwrap_or(next);
+    }
+
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(bytes: &[u8]) -> String {
+        decode(bytes, 0).text
+    }
+
+    #[test]
+    fn lengths() {
+        assert_eq!(len(0x00), 1);
+        assert_eq!(len(0xCB), 2);
+        assert_eq!(len(0x3E), 2);
+        assert_eq!(len(0xE0), 2);
+        assert_eq!(len(0x21), 3);
+        assert_eq!(len(0xCD), 3);
+        assert_eq!(len(0xFA), 3);
+        assert_eq!(len(0xE9), 1);
+    }
+
+    #[test]
+    fn operands() {
+        assert_eq!(text(&[0x08, 0x34, 0x12]), "LD ($1234),SP");
+        assert_eq!(text(&[0x21, 0x00, 0xC0]), "LD HL,$C000");
+        assert_eq!(text(&[0x3A]), "LD A,(HL-)");
+        assert_eq!(text(&[0x36, 0x7F]), "LD (HL),$7F");
+        assert_eq!(text(&[0x76]), "HALT");
+        assert_eq!(text(&[0x78]), "LD A,B");
+        assert_eq!(text(&[0xAF]), "XOR A");
+        assert_eq!(text(&[0xE0, 0x40]), "LDH ($FF40),A");
+        assert_eq!(text(&[0xF8, 0xFE]), "LD HL,SP-2");
+        assert_eq!(text(&[0xE8, 0x02]), "ADD SP,2");
+        assert_eq!(text(&[0xFE, 0x90]), "CP $90");
+        assert_eq!(text(&[0xCB, 0x7C]), "BIT 7,H");
+        assert_eq!(text(&[0xCB, 0x37]), "SWAP A");
+        assert_eq!(text(&[0xD3]), "DB $D3");
+    }
+
+    #[test]
+    fn targets() {
+        let mut memory = vec![0; 0x200];
+        memory[0x150..0x152].copy_from_slice(&[0x20, 0xFE]);
+        memory[0x152..0x155].copy_from_slice(&[0xCD, 0x00, 0x40]);
+        memory[0x155] = 0xFF;
+        memory[0x156] = 0xC9;
+
+        let jr = decode(&memory, 0x150);
+        assert_eq!((jr.text.as_str(), jr.target), ("JR NZ,$0150", Some(0x150)));
+
+        let call = decode(&memory, 0x152);
+        assert_eq!((call.text.as_str(), call.len, call.target), ("CALL $4000", 3, Some(0x4000)));
+
+        assert_eq!(decode(&memory, 0x155).target, Some(0x38));
+        assert_eq!(decode(&memory, 0x156).target, None);
+    }
+
+    #[test]
+    fn anchors_realign_the_sweep() {
+        // LD HL,$0000 at 0, but something jumps to 1
+        let memory = [0x21, 0x00, 0x00, 0x00];
+
+        assert_eq!(&line_starts(&memory, &[])[..3], &[0, 3, 4]);
+        assert_eq!(&line_starts(&memory, &[1])[..4], &[0, 1, 2, 3]);
+        assert_eq!(line_starts(&memory, &[]).last(), Some(&u16::MAX));
+    }
+}
@@ -0,0 +1,227 @@
+//! Interrupts, register writes, DMA and HALT/STOP, logged with where in the frame they happened.
+//!
+//! The CPU doesn't report any of this, so the runner works it out around each step: writes come
+//! from decoding the instruction before it runs, requests from IF bits that turn on, and services
+//! from PC landing on an interrupt vector with the old PC pushed. Dots are counted from when LY last
+//! changed, so they're only as precise as the instruction that crossed the line.
+
+use gbc::{memory::Memory, Gbc, Mmu};
+
+use crate::access;
+
+/// Dots per scanline, and the most a frame's grid is wide
+pub const DOTS: u16 = 456;
+/// Scanlines per frame, counting VBlank
+pub const LINES: u8 = 154;
+
+const IF: u16 = 0xFF0F;
+const LY: u16 = 0xFF44;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    pub const ALL: [Self; 5] = [Self::VBlank, Self::Stat, Self::Timer, Self::Serial, Self::Joypad];
+
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    pub fn vector(self) -> u16 {
+        0x40 + self as u16 * 8
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    InterruptRequest(Interrupt),
+    InterruptService(Interrupt),
+    /// A write to a PPU, APU or timer register, with the value written
+    Write { addr: u16, value: u8 },
+    /// OAM DMA from `source`, or a CGB HDMA transfer when `hdma` is set
+    Dma { source: u16, hdma: bool },
+    Halt,
+    Stop,
+}
+
+/// What events get filtered and colored by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    Interrupt,
+    Ppu,
+    Apu,
+    Timer,
+    Dma,
+    Halt,
+}
+
+impl Category {
+    pub const ALL: [Self; 6] = [Self::Interrupt, Self::Ppu, Self::Apu, Self::Timer, Self::Dma, Self::Halt];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Interrupt => "Interrupts",
+            Self::Ppu => "PPU writes",
+            Self::Apu => "APU writes",
+            Self::Timer => "Timer writes",
+            Self::Dma => "DMA",
+            Self::Halt => "HALT/STOP",
+        }
+    }
+
+    /// Which register writes get logged, if any
+    fn of_register(addr: u16) -> Option<Self> {
+        match addr {
+            0xFF04..=0xFF07 => Some(Self::Timer),
+            0xFF10..=0xFF3F => Some(Self::Apu),
+            0xFF46 | 0xFF55 => Some(Self::Dma),
+            0xFF40..=0xFF4B | 0xFF4F | 0xFF68..=0xFF6B => Some(Self::Ppu),
+            _ => None,
+        }
+    }
+}
+
+impl EventKind {
+    pub fn category(&self) -> Category {
+        match *self {
+            Self::InterruptRequest(_) | Self::InterruptService(_) => Category::Interrupt,
+            Self::Write { addr, .. } => Category::of_register(addr).unwrap_or(Category::Ppu),
+            Self::Dma { .. } => Category::Dma,
+            Self::Halt | Self::Stop => Category::Halt,
+        }
+    }
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InterruptRequest(interrupt) => write!(f, "{interrupt:?} requested"),
+            Self::InterruptService(interrupt) => write!(f, "{interrupt:?} serviced"),
+            Self::Write { addr, value } => write!(f, "${addr:04X} = ${value:02X}"),
+            Self::Dma { source, hdma: false } => write!(f, "OAM DMA from ${source:04X}"),
+            Self::Dma { source, hdma: true } => write!(f, "HDMA from ${source:04X}"),
+            Self::Halt => write!(f, "HALT"),
+            Self::Stop => write!(f, "STOP"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub frame: usize,
+    pub ly: u8,
+    pub dot: u16,
+    /// Of the instruction that caused it, or where the CPU was when an interrupt came in
+    pub pc: u16,
+    pub kind: EventKind,
+}
+
+/// What the logger needs from before a step to make sense of what happened during it
+#[derive(Clone, Copy, Debug)]
+pub struct PreStep {
+    pc: u16,
+    sp: u16,
+    writes: [Option<access::Write>; 2],
+    /// Where the instruction calls, so a CALL to a vector isn't taken for an interrupt
+    call: Option<u16>,
+    halted: bool,
+    stopped: bool,
+    interrupt_flags: u8,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EventLogger {
+    events: Vec<Event>,
+    ly: u8,
+    /// Cycle count when LY last changed
+    line_start: u64,
+}
+
+impl EventLogger {
+    pub fn before_step(&self, emu: &Gbc<Mmu>) -> PreStep {
+        let regs = &emu.cpu.regs;
+        let memory = &emu.cpu.memory;
+        let byte = |offset: u16| memory.load(regs.pc.wrapping_add(offset)).unwrap_or(0);
+
+        PreStep {
+            pc: regs.pc,
+            sp: regs.sp,
+            writes: access::next(emu).writes,
+            call: matches!(byte(0), 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC).then(|| u16::from_le_bytes([byte(1), byte(2)])),
+            halted: emu.cpu.halted,
+            stopped: emu.cpu.stop,
+            interrupt_flags: memory.load(IF).unwrap_or(0),
+        }
+    }
+
+    /// Logs what happened in the step since `before`, with `cycles` being the count before it ran
+    pub fn after_step(&mut self, emu: &Gbc<Mmu>, before: PreStep, frame: usize, cycles: u64) {
+        let memory = &emu.cpu.memory;
+        let ly = memory.load(LY).unwrap_or(0);
+
+        if ly != self.ly {
+            self.ly = ly;
+            self.line_start = cycles;
+        }
+
+        let dot = (cycles - self.line_start).min(DOTS as u64 - 1) as u16;
+        let mut log = |pc: u16, kind: EventKind| self.events.push(Event { frame, ly, dot, pc, kind });
+
+        // IME isn't checked from before the step, since an interrupt can be taken on the step right
+        // after EI, before IME has been seen to turn on
+        let regs = &emu.cpu.regs;
+        let serviced = Interrupt::ALL.into_iter().find(|interrupt| {
+            regs.pc == interrupt.vector()
+                && !regs.ime
+                && regs.sp == before.sp.wrapping_sub(2)
+                && before.call != Some(interrupt.vector())
+        });
+        // one that came in and got serviced straight away never shows up in IF
+        let flags = memory.load(IF).unwrap_or(0) | serviced.map_or(0, Interrupt::bit);
+
+        for interrupt in Interrupt::ALL {
+            if flags & !before.interrupt_flags & interrupt.bit() != 0 {
+                log(before.pc, EventKind::InterruptRequest(interrupt));
+            }
+        }
+
+        if let Some(interrupt) = serviced {
+            log(before.pc, EventKind::InterruptService(interrupt));
+        }
+
+        // the instruction didn't run if an interrupt was serviced instead
+        let writes = before.writes.into_iter().flatten().filter(|write| serviced.is_none() && Category::of_register(write.addr).is_some());
+
+        for access::Write { addr, value } in writes {
+            let kind = match addr {
+                0xFF46 => EventKind::Dma { source: (value as u16) << 8, hdma: false },
+                0xFF55 => {
+                    let source = u16::from_be_bytes([memory.load(0xFF51).unwrap_or(0), memory.load(0xFF52).unwrap_or(0)]) & 0xFFF0;
+                    EventKind::Dma { source, hdma: true }
+                },
+                _ => EventKind::Write { addr, value },
+            };
+
+            log(before.pc, kind);
+        }
+
+        if emu.cpu.halted && !before.halted {
+            log(before.pc, EventKind::Halt);
+        }
+
+        if emu.cpu.stop && !before.stopped {
+            log(before.pc, EventKind::Stop);
+        }
+    }
+
+    /// Everything logged since the last call
+    pub fn take(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,468 @@
+//! Expressions for conditional breakpoints, e.g. `pc == $4A10 && a > 3 && [hl] == $FF && ly == 144`.
+//!
+//! Numbers are decimal, or hex with a `$` or `0x` prefix, or binary with a `%` prefix.
+//! Names are registers (`a`-`l`, `af`, `bc`, `de`, `hl`, `sp`, `pc`), flags (`zf`, `nf`, `hf`, `cf`, `ime`)
+//! and IO registers (`ly`, `lcdc`, `stat`, ...). `[expr]` reads a byte from memory.
+//! Operators are C-like, and comparisons and logic evaluate to 1 or 0.
+
+use std::fmt::Display;
+
+use gbc::{memory::Memory, Gbc};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Var {
+    A, B, C, D, E, F, H, L,
+    Af, Bc, De, Hl,
+    Sp, Pc,
+    Zero, Subtract, HalfCarry, Carry,
+    Ime,
+    /// IO registers are read straight from memory
+    Io(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    BitNot,
+    Neg,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or, And,
+    BitOr, BitXor, BitAnd,
+    Eq, Ne,
+    Lt, Le, Gt, Ge,
+    Shl, Shr,
+    Add, Sub,
+}
+
+impl BinaryOp {
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::BitOr => 3,
+            Self::BitXor => 4,
+            Self::BitAnd => 5,
+            Self::Eq | Self::Ne => 6,
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => 7,
+            Self::Shl | Self::Shr => 8,
+            Self::Add | Self::Sub => 9,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Num(i64),
+    Var(Var),
+    /// A byte from memory
+    Deref(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExprError {
+    UnexpectedEnd,
+    UnexpectedChar(char),
+    Unexpected(String),
+    UnknownName(String),
+    BadNumber(String),
+}
+
+impl Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            Self::UnexpectedChar(c) => write!(f, "unexpected character '{c}'"),
+            Self::Unexpected(token) => write!(f, "unexpected '{token}'"),
+            Self::UnknownName(name) => write!(f, "unknown name '{name}'"),
+            Self::BadNumber(number) => write!(f, "bad number '{number}'"),
+        }
+    }
+}
+
+/// What an expression gets evaluated against
+pub trait Context {
+    fn regs(&self) -> &gbc::Registers;
+    fn load(&self, addr: u16) -> u8;
+}
+
+impl<M: Memory> Context for Gbc<M> {
+    fn regs(&self) -> &gbc::Registers {
+        &self.cpu.regs
+    }
+
+    fn load(&self, addr: u16) -> u8 {
+        self.cpu.memory.load(addr).unwrap_or(0xFF)
+    }
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Self, ExprError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.expr(0)?;
+
+        match parser.tokens.get(parser.pos) {
+            Some(token) => Err(ExprError::Unexpected(token.to_string())),
+            None => Ok(expr),
+        }
+    }
+
+    pub fn eval(&self, ctx: &impl Context) -> i64 {
+        match self {
+            Self::Num(value) => *value,
+            Self::Var(var) => eval_var(*var, ctx),
+            Self::Deref(addr) => ctx.load(addr.eval(ctx) as u16) as i64,
+            Self::Unary(op, value) => {
+                let value = value.eval(ctx);
+
+                match op {
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::BitNot => !value,
+                    UnaryOp::Neg => value.wrapping_neg(),
+                }
+            },
+            // short circuit, so `[hl]` isn't read when it doesn't matter
+            Self::Binary(BinaryOp::And, lhs, rhs) => (lhs.eval(ctx) != 0 && rhs.eval(ctx) != 0) as i64,
+            Self::Binary(BinaryOp::Or, lhs, rhs) => (lhs.eval(ctx) != 0 || rhs.eval(ctx) != 0) as i64,
+            Self::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(ctx), rhs.eval(ctx));
+
+                match op {
+                    BinaryOp::BitOr => lhs | rhs,
+                    BinaryOp::BitXor => lhs ^ rhs,
+                    BinaryOp::BitAnd => lhs & rhs,
+                    BinaryOp::Eq => (lhs == rhs) as i64,
+                    BinaryOp::Ne => (lhs != rhs) as i64,
+                    BinaryOp::Lt => (lhs < rhs) as i64,
+                    BinaryOp::Le => (lhs <= rhs) as i64,
+                    BinaryOp::Gt => (lhs > rhs) as i64,
+                    BinaryOp::Ge => (lhs >= rhs) as i64,
+                    BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            },
+        }
+    }
+
+    /// The address this expression breaks at if it can only ever be true at a single PC,
+    /// e.g. `pc == $4A10 && ...`, so the runner can skip evaluating it everywhere else
+    pub fn pc(&self) -> Option<u16> {
+        match self {
+            Self::Binary(BinaryOp::Eq, lhs, rhs) => match (&**lhs, &**rhs) {
+                (Self::Var(Var::Pc), Self::Num(addr))
+                | (Self::Num(addr), Self::Var(Var::Pc)) => Some(*addr as u16),
+                _ => None,
+            },
+            Self::Binary(BinaryOp::And, lhs, rhs) => lhs.pc().or_else(|| rhs.pc()),
+            _ => None,
+        }
+    }
+}
+
+fn eval_var(var: Var, ctx: &impl Context) -> i64 {
+    let regs = ctx.regs();
+    let f = regs.f.as_byte();
+    let pair = |hi: u8, lo: u8| u16::from_be_bytes([hi, lo]) as i64;
+
+    match var {
+        Var::A => regs.a as i64,
+        Var::B => regs.b as i64,
+        Var::C => regs.c as i64,
+        Var::D => regs.d as i64,
+        Var::E => regs.e as i64,
+        Var::F => f as i64,
+        Var::H => regs.h as i64,
+        Var::L => regs.l as i64,
+        Var::Af => pair(regs.a, f),
+        Var::Bc => pair(regs.b, regs.c),
+        Var::De => pair(regs.d, regs.e),
+        Var::Hl => pair(regs.h, regs.l),
+        Var::Sp => regs.sp as i64,
+        Var::Pc => regs.pc as i64,
+        Var::Zero => (f >> 7 & 1) as i64,
+        Var::Subtract => (f >> 6 & 1) as i64,
+        Var::HalfCarry => (f >> 5 & 1) as i64,
+        Var::Carry => (f >> 4 & 1) as i64,
+        Var::Ime => regs.ime as i64,
+        Var::Io(addr) => ctx.load(addr) as i64,
+    }
+}
+
+/// Whether `name` means something to expressions, so it can't be taken as a label
+pub fn is_var(name: &str) -> bool {
+    lookup(&name.to_ascii_lowercase()).is_some()
+}
+
+fn lookup(name: &str) -> Option<Var> {
+    Some(match name {
+        "a" => Var::A,
+        "b" => Var::B,
+        "c" => Var::C,
+        "d" => Var::D,
+        "e" => Var::E,
+        "f" => Var::F,
+        "h" => Var::H,
+        "l" => Var::L,
+        "af" => Var::Af,
+        "bc" => Var::Bc,
+        "de" => Var::De,
+        "hl" => Var::Hl,
+        "sp" => Var::Sp,
+        "pc" => Var::Pc,
+        "zf" => Var::Zero,
+        "nf" => Var::Subtract,
+        "hf" => Var::HalfCarry,
+        "cf" => Var::Carry,
+        "ime" => Var::Ime,
+        "joyp" => Var::Io(0xFF00),
+        "sb" => Var::Io(0xFF01),
+        "sc" => Var::Io(0xFF02),
+        "div" => Var::Io(0xFF04),
+        "tima" => Var::Io(0xFF05),
+        "tma" => Var::Io(0xFF06),
+        "tac" => Var::Io(0xFF07),
+        "if" => Var::Io(0xFF0F),
+        "lcdc" => Var::Io(0xFF40),
+        "stat" => Var::Io(0xFF41),
+        "scy" => Var::Io(0xFF42),
+        "scx" => Var::Io(0xFF43),
+        "ly" => Var::Io(0xFF44),
+        "lyc" => Var::Io(0xFF45),
+        "dma" => Var::Io(0xFF46),
+        "bgp" => Var::Io(0xFF47),
+        "obp0" => Var::Io(0xFF48),
+        "obp1" => Var::Io(0xFF49),
+        "wy" => Var::Io(0xFF4A),
+        "wx" => Var::Io(0xFF4B),
+        "key1" => Var::Io(0xFF4D),
+        "vbk" => Var::Io(0xFF4F),
+        "svbk" => Var::Io(0xFF70),
+        "ie" => Var::Io(0xFFFF),
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Num(value) => write!(f, "{value}"),
+            Self::Name(name) => write!(f, "{name}"),
+            Self::Op(op) => write!(f, "{op}"),
+        }
+    }
+}
+
+/// Longest first, so `<=` doesn't get read as `<` then `=`
+const OPS: [&str; 22] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>",
+    "|", "^", "&", "<", ">", "+", "-", "!", "~", "(", ")", "[", "]", "=",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        if c == '$' || c == '%' || c.is_ascii_digit() {
+            let len = rest[1..].find(|c: char| !c.is_ascii_alphanumeric()).map(|i| i + 1).unwrap_or(rest.len());
+            let (number, tail) = rest.split_at(len);
+            tokens.push(Token::Num(parse_number(number)?));
+            rest = tail;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            let (name, tail) = rest.split_at(len);
+            tokens.push(Token::Name(name.to_ascii_lowercase()));
+            rest = tail;
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            // a lone `=` is taken to mean `==`
+            tokens.push(Token::Op(if *op == "=" { "==" } else { op }));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(ExprError::UnexpectedChar(c));
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(number: &str) -> Result<i64, ExprError> {
+    let parsed = if let Some(hex) = number.strip_prefix('$').or_else(|| number.strip_prefix("0x")) {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = number.strip_prefix('%') {
+        i64::from_str_radix(bin, 2)
+    } else {
+        number.parse()
+    };
+
+    parsed.map_err(|_| ExprError::BadNumber(number.to_owned()))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token, ExprError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(ExprError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), ExprError> {
+        match self.next()? {
+            Token::Op(found) if found == op => Ok(()),
+            token => Err(ExprError::Unexpected(token.to_string())),
+        }
+    }
+
+    fn peek_binary(&self) -> Option<BinaryOp> {
+        let Some(Token::Op(op)) = self.tokens.get(self.pos) else {
+            return None;
+        };
+
+        Some(match *op {
+            "||" => BinaryOp::Or,
+            "&&" => BinaryOp::And,
+            "|" => BinaryOp::BitOr,
+            "^" => BinaryOp::BitXor,
+            "&" => BinaryOp::BitAnd,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "<<" => BinaryOp::Shl,
+            ">>" => BinaryOp::Shr,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            _ => return None,
+        })
+    }
+
+    /// Precedence climbing, only taking operators that bind tighter than `min`
+    fn expr(&mut self, min: u8) -> Result<Expr, ExprError> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.peek_binary() {
+            if op.precedence() <= min {
+                break;
+            }
+
+            self.pos += 1;
+            let rhs = self.expr(op.precedence())?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        match self.next()? {
+            Token::Num(value) => Ok(Expr::Num(value)),
+            Token::Name(name) => lookup(&name).map(Expr::Var).ok_or(ExprError::UnknownName(name)),
+            Token::Op("!") => Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?))),
+            Token::Op("~") => Ok(Expr::Unary(UnaryOp::BitNot, Box::new(self.unary()?))),
+            Token::Op("-") => Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?))),
+            Token::Op("(") => {
+                let expr = self.expr(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            },
+            Token::Op("[") => {
+                let expr = self.expr(0)?;
+                self.expect("]")?;
+                Ok(Expr::Deref(Box::new(expr)))
+            },
+            token => Err(ExprError::Unexpected(token.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gbc::memory::FlatMemory;
+
+    use super::*;
+
+    fn emu() -> Gbc<FlatMemory> {
+        let mut emu = Gbc::new_flat(true, true);
+        emu.cpu.regs.a = 5;
+        emu.cpu.regs.h = 0xC0;
+        emu.cpu.regs.l = 0x10;
+        emu.cpu.regs.pc = 0x4A10;
+        emu.cpu.regs.f.set_bits(0x90);
+        emu.cpu.memory.set(0xC010, 0xFF);
+        emu.cpu.memory.set(0xFF44, 144);
+        emu
+    }
+
+    fn eval(input: &str) -> i64 {
+        Expr::parse(input).unwrap().eval(&emu())
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(eval("12"), 12);
+        assert_eq!(eval("$ff"), 0xFF);
+        assert_eq!(eval("0x4A10"), 0x4A10);
+        assert_eq!(eval("%101"), 5);
+        assert_eq!(Expr::parse("$G"), Err(ExprError::BadNumber("$G".to_owned())));
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 == 3 && 4 > 3"), 1);
+        // 1 | (2 ^ (3 & 6))
+        assert_eq!(eval("1 | 2 ^ 3 & 6"), 1);
+        assert_eq!(eval("1 << 2 + 1"), 8);
+        assert_eq!(eval("10 - 2 - 3"), 5);
+        assert_eq!(eval("-(1 + 2) + ~0 + !0"), -3);
+    }
+
+    #[test]
+    fn vars() {
+        assert_eq!(eval("hl"), 0xC010);
+        assert_eq!(eval("f"), 0x90);
+        assert_eq!(eval("zf + nf + cf"), 2);
+        assert_eq!(eval("[hl]"), 0xFF);
+        assert_eq!(eval("LY"), 144);
+        assert_eq!(eval("pc == $4A10 && a > 3 && [hl] == $FF && ly = 144"), 1);
+        assert!(is_var("SP") && !is_var("main"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Expr::parse("a +"), Err(ExprError::UnexpectedEnd));
+        assert_eq!(Expr::parse("(a"), Err(ExprError::UnexpectedEnd));
+        assert_eq!(Expr::parse("a b"), Err(ExprError::Unexpected("b".to_owned())));
+        assert_eq!(Expr::parse("a # 1"), Err(ExprError::UnexpectedChar('#')));
+        assert_eq!(Expr::parse("foo == 1"), Err(ExprError::UnknownName("foo".to_owned())));
+    }
+
+    #[test]
+    fn pc() {
+        assert_eq!(Expr::parse("pc == $4A10 && a > 3").unwrap().pc(), Some(0x4A10));
+        assert_eq!(Expr::parse("a > 3 && $150 == pc").unwrap().pc(), Some(0x150));
+        assert_eq!(Expr::parse("pc == $4A10 || a > 3").unwrap().pc(), None);
+        assert_eq!(Expr::parse("pc != $4A10").unwrap().pc(), None);
+    }
+}
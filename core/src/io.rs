@@ -0,0 +1,230 @@
+//! Every IO register from $FF00-$FF7F plus IE, with what each bit means, for the IO register inspector.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Group {
+    Joypad,
+    Serial,
+    Timer,
+    Interrupts,
+    Sound,
+    Ppu,
+    Dma,
+    /// Set up once at boot
+    System,
+    Cgb,
+}
+
+impl Group {
+    pub const ALL: [Self; 9] = [Self::Joypad, Self::Serial, Self::Timer, Self::Interrupts, Self::Sound, Self::Ppu, Self::Dma, Self::System, Self::Cgb];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Joypad => "Joypad",
+            Self::Serial => "Serial",
+            Self::Timer => "Timer",
+            Self::Interrupts => "Interrupts",
+            Self::Sound => "Sound",
+            Self::Ppu => "PPU",
+            Self::Dma => "DMA",
+            Self::System => "System",
+            Self::Cgb => "CGB",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    /// A single bit that's on or off
+    Flag,
+    /// One name per value
+    Names(&'static [&'static str]),
+    /// Shown as a number
+    Value,
+}
+
+/// Bits `lo..=hi` of a register
+#[derive(Clone, Copy, Debug)]
+pub struct Field {
+    pub hi: u8,
+    pub lo: u8,
+    pub name: &'static str,
+    pub kind: Kind,
+}
+
+impl Field {
+    pub fn get(&self, value: u8) -> u8 {
+        let width = self.hi - self.lo + 1;
+        (value >> self.lo) & (0xFF >> (8 - width))
+    }
+
+    /// Like `BG tile data: $8000`
+    pub fn describe(&self, value: u8) -> String {
+        let bits = self.get(value);
+
+        match self.kind {
+            Kind::Flag => format!("{}: {}", self.name, if bits != 0 { "on" } else { "off" }),
+            Kind::Names(names) => format!("{}: {}", self.name, names.get(bits as usize).unwrap_or(&"?")),
+            Kind::Value => format!("{}: {bits}", self.name),
+        }
+    }
+
+    /// Bit numbers, like `4` or `6-4`
+    pub fn bits(&self) -> String {
+        match self.hi == self.lo {
+            true => self.hi.to_string(),
+            false => format!("{}-{}", self.hi, self.lo),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Register {
+    pub addr: u16,
+    pub name: &'static str,
+    pub group: Group,
+    /// Empty when the whole byte is just a number
+    pub fields: &'static [Field],
+}
+
+const fn flag(bit: u8, name: &'static str) -> Field {
+    Field { hi: bit, lo: bit, name, kind: Kind::Flag }
+}
+
+const fn names(hi: u8, lo: u8, name: &'static str, names: &'static [&'static str]) -> Field {
+    Field { hi, lo, name, kind: Kind::Names(names) }
+}
+
+const fn value(hi: u8, lo: u8, name: &'static str) -> Field {
+    Field { hi, lo, name, kind: Kind::Value }
+}
+
+const fn reg(addr: u16, name: &'static str, group: Group, fields: &'static [Field]) -> Register {
+    Register { addr, name, group, fields }
+}
+
+const INTERRUPTS: &[Field] = &[flag(4, "Joypad"), flag(3, "Serial"), flag(2, "Timer"), flag(1, "STAT"), flag(0, "VBlank")];
+const MAP: &[&str] = &["$9800", "$9C00"];
+const PULSE_LENGTH: &[Field] = &[names(7, 6, "Duty", &["12.5%", "25%", "50%", "75%"]), value(5, 0, "Length")];
+const ENVELOPE: &[Field] = &[value(7, 4, "Initial volume"), names(3, 3, "Envelope", &["down", "up"]), value(2, 0, "Envelope pace")];
+const PERIOD_HIGH: &[Field] = &[flag(7, "Trigger"), flag(6, "Length enable"), value(2, 0, "Period high")];
+const PALETTE: &[Field] = &[value(7, 6, "Color 3"), value(5, 4, "Color 2"), value(3, 2, "Color 1"), value(1, 0, "Color 0")];
+const PALETTE_SPEC: &[Field] = &[flag(7, "Auto increment"), value(5, 0, "Address")];
+
+pub const REGISTERS: &[Register] = &[
+    reg(0xFF00, "P1", Group::Joypad, &[
+        names(5, 5, "Buttons", &["selected", "not selected"]),
+        names(4, 4, "D-pad", &["selected", "not selected"]),
+        names(3, 3, "Start/Down", &["pressed", "released"]),
+        names(2, 2, "Select/Up", &["pressed", "released"]),
+        names(1, 1, "B/Left", &["pressed", "released"]),
+        names(0, 0, "A/Right", &["pressed", "released"]),
+    ]),
+    reg(0xFF01, "SB", Group::Serial, &[]),
+    reg(0xFF02, "SC", Group::Serial, &[
+        names(7, 7, "Transfer", &["idle", "requested"]),
+        names(1, 1, "Clock speed", &["normal", "fast (CGB)"]),
+        names(0, 0, "Clock", &["external", "internal"]),
+    ]),
+    reg(0xFF04, "DIV", Group::Timer, &[]),
+    reg(0xFF05, "TIMA", Group::Timer, &[]),
+    reg(0xFF06, "TMA", Group::Timer, &[]),
+    reg(0xFF07, "TAC", Group::Timer, &[
+        flag(2, "Enable"),
+        names(1, 0, "Clock", &["4096 Hz", "262144 Hz", "65536 Hz", "16384 Hz"]),
+    ]),
+    reg(0xFF0F, "IF", Group::Interrupts, INTERRUPTS),
+    reg(0xFFFF, "IE", Group::Interrupts, INTERRUPTS),
+    reg(0xFF10, "NR10", Group::Sound, &[value(6, 4, "Sweep pace"), names(3, 3, "Sweep", &["up", "down"]), value(2, 0, "Sweep step")]),
+    reg(0xFF11, "NR11", Group::Sound, PULSE_LENGTH),
+    reg(0xFF12, "NR12", Group::Sound, ENVELOPE),
+    reg(0xFF13, "NR13", Group::Sound, &[]),
+    reg(0xFF14, "NR14", Group::Sound, PERIOD_HIGH),
+    reg(0xFF16, "NR21", Group::Sound, PULSE_LENGTH),
+    reg(0xFF17, "NR22", Group::Sound, ENVELOPE),
+    reg(0xFF18, "NR23", Group::Sound, &[]),
+    reg(0xFF19, "NR24", Group::Sound, PERIOD_HIGH),
+    reg(0xFF1A, "NR30", Group::Sound, &[flag(7, "DAC")]),
+    reg(0xFF1B, "NR31", Group::Sound, &[]),
+    reg(0xFF1C, "NR32", Group::Sound, &[names(6, 5, "Output level", &["mute", "100%", "50%", "25%"])]),
+    reg(0xFF1D, "NR33", Group::Sound, &[]),
+    reg(0xFF1E, "NR34", Group::Sound, PERIOD_HIGH),
+    reg(0xFF20, "NR41", Group::Sound, &[value(5, 0, "Length")]),
+    reg(0xFF21, "NR42", Group::Sound, ENVELOPE),
+    reg(0xFF22, "NR43", Group::Sound, &[value(7, 4, "Clock shift"), names(3, 3, "LFSR width", &["15 bit", "7 bit"]), value(2, 0, "Clock divider")]),
+    reg(0xFF23, "NR44", Group::Sound, &[flag(7, "Trigger"), flag(6, "Length enable")]),
+    reg(0xFF24, "NR50", Group::Sound, &[flag(7, "VIN left"), value(6, 4, "Left volume"), flag(3, "VIN right"), value(2, 0, "Right volume")]),
+    reg(0xFF25, "NR51", Group::Sound, &[
+        flag(7, "CH4 left"), flag(6, "CH3 left"), flag(5, "CH2 left"), flag(4, "CH1 left"),
+        flag(3, "CH4 right"), flag(2, "CH3 right"), flag(1, "CH2 right"), flag(0, "CH1 right"),
+    ]),
+    reg(0xFF26, "NR52", Group::Sound, &[flag(7, "Audio"), flag(3, "CH4 on"), flag(2, "CH3 on"), flag(1, "CH2 on"), flag(0, "CH1 on")]),
+    reg(0xFF40, "LCDC", Group::Ppu, &[
+        flag(7, "LCD"),
+        names(6, 6, "Window map", MAP),
+        flag(5, "Window"),
+        names(4, 4, "BG tile data", &["$8800", "$8000"]),
+        names(3, 3, "BG map", MAP),
+        names(2, 2, "OBJ size", &["8x8", "8x16"]),
+        flag(1, "OBJ"),
+        flag(0, "BG/window"),
+    ]),
+    reg(0xFF41, "STAT", Group::Ppu, &[
+        flag(6, "LYC interrupt"),
+        flag(5, "Mode 2 interrupt"),
+        flag(4, "Mode 1 interrupt"),
+        flag(3, "Mode 0 interrupt"),
+        flag(2, "LY == LYC"),
+        names(1, 0, "Mode", &["HBlank", "VBlank", "OAM scan", "Drawing"]),
+    ]),
+    reg(0xFF42, "SCY", Group::Ppu, &[]),
+    reg(0xFF43, "SCX", Group::Ppu, &[]),
+    reg(0xFF44, "LY", Group::Ppu, &[]),
+    reg(0xFF45, "LYC", Group::Ppu, &[]),
+    reg(0xFF47, "BGP", Group::Ppu, PALETTE),
+    reg(0xFF48, "OBP0", Group::Ppu, PALETTE),
+    reg(0xFF49, "OBP1", Group::Ppu, PALETTE),
+    reg(0xFF4A, "WY", Group::Ppu, &[]),
+    reg(0xFF4B, "WX", Group::Ppu, &[]),
+    reg(0xFF46, "DMA", Group::Dma, &[]),
+    reg(0xFF51, "HDMA1", Group::Dma, &[]),
+    reg(0xFF52, "HDMA2", Group::Dma, &[]),
+    reg(0xFF53, "HDMA3", Group::Dma, &[]),
+    reg(0xFF54, "HDMA4", Group::Dma, &[]),
+    reg(0xFF55, "HDMA5", Group::Dma, &[names(7, 7, "Mode", &["general", "HBlank"]), value(6, 0, "Blocks left - 1")]),
+    reg(0xFF50, "BANK", Group::System, &[names(0, 0, "Boot ROM", &["mapped", "unmapped"])]),
+    reg(0xFF4C, "KEY0", Group::Cgb, &[names(2, 2, "CPU mode", &["CGB", "DMG compatibility"])]),
+    reg(0xFF4D, "KEY1", Group::Cgb, &[names(7, 7, "Speed", &["normal", "double"]), flag(0, "Switch armed")]),
+    reg(0xFF4F, "VBK", Group::Cgb, &[value(0, 0, "VRAM bank")]),
+    reg(0xFF56, "RP", Group::Cgb, &[value(7, 6, "Read enable"), names(1, 1, "Receiving", &["yes", "no"]), flag(0, "LED")]),
+    reg(0xFF68, "BCPS", Group::Cgb, PALETTE_SPEC),
+    reg(0xFF69, "BCPD", Group::Cgb, &[]),
+    reg(0xFF6A, "OCPS", Group::Cgb, PALETTE_SPEC),
+    reg(0xFF6B, "OCPD", Group::Cgb, &[]),
+    reg(0xFF6C, "OPRI", Group::Cgb, &[names(0, 0, "OBJ priority", &["OAM order", "X position"])]),
+    reg(0xFF70, "SVBK", Group::Cgb, &[value(2, 0, "WRAM bank")]),
+    reg(0xFF76, "PCM12", Group::Cgb, &[value(7, 4, "CH2 output"), value(3, 0, "CH1 output")]),
+    reg(0xFF77, "PCM34", Group::Cgb, &[value(7, 4, "CH4 output"), value(3, 0, "CH3 output")]),
+];
+
+/// Wave pattern RAM, shown on its own since it's 32 samples rather than bitfields
+pub const WAVE_RAM: (u16, u16) = (0xFF30, 0xFF3F);
+
+/// Registers in `group`, in the order they're listed
+pub fn group(group: Group) -> impl Iterator<Item = &'static Register> {
+    REGISTERS.iter().filter(move |reg| reg.group == group)
+}
+
+/// The IO registers out of a full memory snapshot, $FF00-$FF7F then IE
+pub fn snapshot(memory: &[u8]) -> Vec<u8> {
+    let mut io = memory.get(0xFF00..0xFF80).map(<[u8]>::to_vec).unwrap_or_default();
+    io.push(memory.get(0xFFFF).copied().unwrap_or(0));
+    io
+}
+
+/// Where `addr` is in a [`snapshot`]
+pub fn snapshot_index(addr: u16) -> usize {
+    match addr {
+        0xFFFF => 0x80,
+        _ => (addr - 0xFF00) as usize,
+    }
+}
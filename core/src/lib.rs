@@ -0,0 +1,31 @@
+
+//! The emulator runner and the message protocol used to drive it, independent of any UI.
+//!
+//! Frames and status changes come out through a [`sink::FrameSink`], so the same [`runner::Emu`]
+//! can be driven by a window or run headless.
+
+pub mod access;
+pub mod apu;
+pub mod audio;
+pub mod battery;
+pub mod breakpoints;
+pub mod cheats;
+pub mod comms;
+pub mod disasm;
+pub mod events;
+pub mod expr;
+pub mod io;
+pub mod lines;
+pub mod mbc;
+pub mod movie;
+pub mod oam;
+pub mod pacing;
+pub mod rewind;
+pub mod runner;
+pub mod savestate;
+pub mod search;
+pub mod sink;
+pub mod state;
+pub mod symbols;
+pub mod trace;
+pub mod vram;
@@ -0,0 +1,71 @@
+//! The line format shared by the .bpt and .cht files, one entry per line as
+//! `<enabled 0/1>\t<label>\t<entry>`, with the last field left to the file to make sense of.
+
+use std::fmt::Display;
+
+/// Adds an entry's line to `out`
+pub fn encode(out: &mut String, enabled: bool, label: &str, entry: impl Display) {
+    // tabs and newlines would break the line up
+    let label = label.replace(['\t', '\n', '\r'], " ");
+    out.push_str(&format!("{}\t{label}\t{entry}\n", enabled as u8));
+}
+
+/// Hands `parse` the enabled flag, label and entry of each line that isn't blank.
+/// Lines that don't parse are reported and skipped, so one bad line doesn't lose the rest
+pub fn decode(text: &str, mut parse: impl FnMut(bool, &str, &str) -> Result<(), String>) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(enabled), Some(label), Some(entry)) = (fields.next(), fields.next(), fields.next()) else {
+            errors.push(format!("line {}: expected 3 fields", i + 1));
+            continue;
+        };
+
+        if let Err(err) = parse(enabled.trim() != "0", label, entry) {
+            errors.push(format!("line {}: {err}", i + 1));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut text = String::new();
+        encode(&mut text, true, "vblank\thandler", "pc 0040");
+        encode(&mut text, false, "", 12);
+
+        let mut decoded = Vec::new();
+        let errors = decode(&text, |enabled, label, entry| {
+            decoded.push((enabled, label.to_owned(), entry.to_owned()));
+            Ok(())
+        });
+
+        assert!(errors.is_empty());
+        assert_eq!(decoded, [
+            (true, "vblank handler".to_owned(), "pc 0040".to_owned()),
+            (false, String::new(), "12".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn skips_bad_lines() {
+        let mut good = Vec::new();
+        let errors = decode("1\tok\tgood\n\n1\tbad\tnope\nnot enough fields\n0\talso ok\tgood\n", |enabled, label, entry| {
+            if entry != "good" {
+                return Err(format!("bad entry '{entry}'"));
+            }
+
+            good.push((enabled, label.to_owned()));
+            Ok(())
+        });
+
+        assert_eq!(good, [(true, "ok".to_owned()), (false, "also ok".to_owned())]);
+        assert_eq!(errors, ["line 3: bad entry 'nope'", "line 4: expected 3 fields"]);
+    }
+}
@@ -0,0 +1,497 @@
+//! The cartridge's bank registers, tracked from the writes that set them.
+//!
+//! The core doesn't expose its mapper, so the runner watches writes to $0000-$7FFF and keeps its
+//! own copy. That's enough to show which ROM bank is mapped, to put the mapper back the way it was
+//! when a save state is loaded, and to walk every bank of cartridge RAM through $A000-$BFFF.
+//!
+//! MBC3's clock is kept the same way: latches and register writes are watched, and [`Mapper::tick`]
+//! counts it on in emulated time, so a .sav can get both register sets without latching anything.
+
+use gbc::memory::Memory;
+
+/// Cartridge type, at $0147 in the header
+const CART_TYPE: usize = 0x147;
+/// RAM size, at $0149 in the header
+const RAM_SIZE: usize = 0x149;
+const RAM_BANK_LEN: usize = 0x2000;
+/// MBC2 has 512 half-bytes built in rather than banks
+const MBC2_RAM_LEN: usize = 512;
+/// MBC3 maps the clock's S, M, H, DL and DH registers in place of RAM banks $08-$0C
+const RTC_REGISTERS: u8 = 0x08;
+/// T-cycles in a second of the clock
+const CYCLES_PER_SECOND: u64 = 4_194_304;
+
+/// DH bits
+const DAY_HIGH: u8 = 0b0000_0001;
+const HALT: u8 = 0b0100_0000;
+const DAY_CARRY: u8 = 0b1000_0000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Kind {
+    #[default]
+    None,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mapper {
+    pub kind: Kind,
+    /// Cartridge RAM in bytes, across every bank
+    pub ram_len: usize,
+    pub battery: bool,
+    pub rtc: bool,
+    /// The value last written to each register, see [`Mapper::registers`]
+    registers: [u8; 5],
+    clock: Clock,
+}
+
+/// MBC3's clock registers, S, M, H, DL and DH
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Clock {
+    /// The registers as they count
+    pub current: [u8; 5],
+    /// The copy made by the last latch, which is what the game reads back
+    pub latched: [u8; 5],
+    /// T-cycles into the current second
+    cycles: u64,
+}
+
+impl Clock {
+    pub fn new(current: [u8; 5], latched: [u8; 5]) -> Self {
+        Self { current, latched, cycles: 0 }
+    }
+
+    /// Moves the current registers on by `seconds`, unless the clock is halted
+    pub fn advance(&mut self, seconds: u64) {
+        let [s, m, h, dl, dh] = self.current;
+
+        if dh & HALT != 0 {
+            return;
+        }
+
+        let days = ((dh & DAY_HIGH) as u64) << 8 | dl as u64;
+        let total = s as u64 + m as u64 * 60 + h as u64 * 3600 + days * 86400 + seconds;
+        let days = total / 86400;
+        let carry = if days > 0x1FF { DAY_CARRY } else { dh & DAY_CARRY };
+
+        self.current = [
+            (total % 60) as u8,
+            (total / 60 % 60) as u8,
+            (total / 3600 % 24) as u8,
+            days as u8,
+            (dh & HALT) | carry | ((days >> 8) as u8 & DAY_HIGH),
+        ];
+    }
+}
+
+const RAM_ENABLE: usize = 0;
+const ROM_LOW: usize = 1;
+/// Bit 8 of the ROM bank on MBC5
+const ROM_HIGH: usize = 2;
+/// The RAM bank, the upper ROM bank bits on MBC1 or an RTC register on MBC3
+const RAM_BANK: usize = 3;
+/// MBC1's banking mode, or MBC3's latch
+const MODE: usize = 4;
+
+impl Mapper {
+    /// The mapper described by `rom`'s header, as it is at power-on
+    pub fn new(rom: &[u8]) -> Self {
+        let cart_type = rom.get(CART_TYPE).copied().unwrap_or(0);
+
+        let kind = match cart_type {
+            0x01..=0x03 => Kind::Mbc1,
+            0x05 | 0x06 => Kind::Mbc2,
+            0x0F..=0x13 => Kind::Mbc3,
+            0x19..=0x1E => Kind::Mbc5,
+            _ => Kind::None,
+        };
+
+        let ram_len = match (kind, rom.get(RAM_SIZE).copied().unwrap_or(0)) {
+            (Kind::Mbc2, _) => MBC2_RAM_LEN,
+            (_, 0x02) => RAM_BANK_LEN,
+            (_, 0x03) => RAM_BANK_LEN * 4,
+            (_, 0x04) => RAM_BANK_LEN * 16,
+            (_, 0x05) => RAM_BANK_LEN * 8,
+            _ => 0,
+        };
+
+        Self {
+            kind,
+            ram_len,
+            battery: matches!(cart_type, 0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E),
+            rtc: matches!(cart_type, 0x0F | 0x10),
+            registers: [0, 1, 0, 0, 0],
+            clock: Clock::default(),
+        }
+    }
+
+    /// The registers as they'd go in a save state
+    pub fn registers(&self) -> [u8; 5] {
+        self.registers
+    }
+
+    pub fn set_registers(&mut self, registers: [u8; 5]) {
+        self.registers = registers;
+    }
+
+    /// Keeps track of a write the CPU made, which only matters if it's to $0000-$7FFF
+    /// or to a clock register
+    pub fn write(&mut self, addr: u16, value: u8) {
+        if self.rtc {
+            self.write_clock(addr, value);
+        }
+
+        let register = match (self.kind, addr) {
+            (Kind::None, _) | (_, 0x8000..=0xFFFF) => return,
+            (Kind::Mbc2, 0x0000..=0x3FFF) if addr & 0x100 == 0 => RAM_ENABLE,
+            (Kind::Mbc2, 0x0000..=0x3FFF) => ROM_LOW,
+            (Kind::Mbc2, _) => return,
+            (_, 0x0000..=0x1FFF) => RAM_ENABLE,
+            (Kind::Mbc5, 0x3000..=0x3FFF) => ROM_HIGH,
+            (_, 0x2000..=0x3FFF) => ROM_LOW,
+            (_, 0x4000..=0x5FFF) => RAM_BANK,
+            (Kind::Mbc5, _) => return,
+            (_, _) => MODE,
+        };
+
+        self.registers[register] = value;
+    }
+
+    fn write_clock(&mut self, addr: u16, value: u8) {
+        match addr {
+            // writing 0 then 1 latches
+            0x6000..=0x7FFF if self.registers[MODE] == 0x00 && value == 0x01 => self.clock.latched = self.clock.current,
+            0xA000..=0xBFFF if self.ram_enabled() => {
+                let Some(register) = self.registers[RAM_BANK].checked_sub(RTC_REGISTERS).filter(|&register| register < 5) else {
+                    return;
+                };
+
+                self.clock.current[register as usize] = value;
+
+                // setting the seconds starts a fresh second
+                if register == 0 {
+                    self.clock.cycles = 0;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Counts the clock on by `cycles` T-cycles of emulated time
+    pub fn tick(&mut self, cycles: u64) {
+        if !self.rtc {
+            return;
+        }
+
+        self.clock.cycles += cycles;
+        if self.clock.cycles >= CYCLES_PER_SECOND {
+            self.clock.advance(self.clock.cycles / CYCLES_PER_SECOND);
+            self.clock.cycles %= CYCLES_PER_SECOND;
+        }
+    }
+
+    /// The clock's registers as tracked, or None if the cartridge doesn't have one
+    pub fn clock(&self) -> Option<Clock> {
+        self.rtc.then_some(self.clock)
+    }
+
+    /// The bank mapped into $4000-$7FFF
+    pub fn rom_bank(&self) -> u16 {
+        let low = self.registers[ROM_LOW] as u16;
+
+        match self.kind {
+            Kind::None => 1,
+            Kind::Mbc1 => (low & 0x1F).max(1) | (self.registers[RAM_BANK] as u16 & 0x03) << 5,
+            Kind::Mbc2 => (low & 0x0F).max(1),
+            Kind::Mbc3 => (low & 0x7F).max(1),
+            Kind::Mbc5 => low | (self.registers[ROM_HIGH] as u16 & 0x01) << 8,
+        }
+    }
+
+    /// Whether $A000-$BFFF reaches cartridge RAM right now
+    pub fn ram_enabled(&self) -> bool {
+        self.kind == Kind::None || self.registers[RAM_ENABLE] & 0x0F == 0x0A
+    }
+
+    /// Where a write to `addr` lands in cartridge RAM as [`Mapper::read_ram`] lays it out, going by the
+    /// bank that's mapped. None if it isn't to cartridge RAM, or RAM is disabled or has a clock register mapped
+    pub fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if !(0xA000..=0xBFFF).contains(&addr) || self.ram_len == 0 || !self.ram_enabled() {
+            return None;
+        }
+
+        let offset = (addr - 0xA000) as usize;
+        let bank = self.registers[RAM_BANK] as usize;
+
+        let offset = match self.kind {
+            Kind::None => offset,
+            // RAM banking only happens in mode 1
+            Kind::Mbc1 if self.registers[MODE] & 1 == 0 => offset,
+            Kind::Mbc1 => (bank & 0x03) * RAM_BANK_LEN + offset,
+            // the 512 half-bytes repeat all the way through
+            Kind::Mbc2 => offset % MBC2_RAM_LEN,
+            Kind::Mbc3 if bank >= RTC_REGISTERS as usize => return None,
+            Kind::Mbc3 => (bank & 0x03) * RAM_BANK_LEN + offset,
+            Kind::Mbc5 => (bank & 0x0F) * RAM_BANK_LEN + offset,
+        };
+
+        // banks past the end of smaller RAMs wrap around
+        Some(offset % self.ram_len)
+    }
+
+    /// Where `addr` in RAM bank `bank` lands in cartridge RAM as [`Mapper::read_ram`] lays it out,
+    /// whatever bank is mapped right now
+    pub fn bank_offset(&self, bank: u8, addr: u16) -> Option<usize> {
+        if !(0xA000..=0xBFFF).contains(&addr) || self.ram_len == 0 {
+            return None;
+        }
+
+        let offset = (addr - 0xA000) as usize;
+
+        let offset = match self.kind {
+            Kind::None => offset,
+            Kind::Mbc2 => offset % MBC2_RAM_LEN,
+            Kind::Mbc1 | Kind::Mbc3 | Kind::Mbc5 => bank as usize * RAM_BANK_LEN + offset,
+        };
+
+        Some(offset % self.ram_len)
+    }
+
+    /// Writes `value` to `addr` in RAM bank `bank`, putting the registers back afterwards
+    pub fn write_bank(&self, memory: &mut impl Memory, bank: u8, addr: u16, value: u8) {
+        self.map_ram(memory, bank);
+        memory.set(addr, value);
+        self.restore(memory);
+    }
+
+    /// Writes every register back into the core, leaving it mapped the way it was when they were tracked
+    pub fn restore(&self, memory: &mut impl Memory) {
+        let [ram_enable, rom_low, rom_high, ram_bank, mode] = self.registers;
+
+        match self.kind {
+            Kind::None => {},
+            Kind::Mbc1 => {
+                memory.set(0x6000, mode);
+                memory.set(0x4000, ram_bank);
+                memory.set(0x2000, rom_low);
+            },
+            Kind::Mbc2 => memory.set(0x0100, rom_low),
+            // the latch isn't written back, since that would latch the clock
+            Kind::Mbc3 => {
+                memory.set(0x4000, ram_bank);
+                memory.set(0x2000, rom_low);
+            },
+            Kind::Mbc5 => {
+                memory.set(0x4000, ram_bank);
+                memory.set(0x3000, rom_high);
+                memory.set(0x2000, rom_low);
+            },
+        }
+
+        if self.kind != Kind::None {
+            memory.set(0x0000, ram_enable);
+        }
+    }
+
+    /// Every bank of cartridge RAM, one after another.
+    /// This switches banks to get at them, and puts the registers back afterwards.
+    pub fn read_ram(&self, memory: &mut impl Memory) -> Vec<u8> {
+        let mut ram = Vec::with_capacity(self.ram_len);
+
+        for bank in 0..self.ram_len.div_ceil(RAM_BANK_LEN) {
+            self.map_ram(memory, bank as u8);
+            let len = (self.ram_len - ram.len()).min(RAM_BANK_LEN) as u16;
+            ram.extend((0xA000..0xA000 + len).map(|addr| memory.load(addr).unwrap_or(0xFF)));
+        }
+
+        self.restore(memory);
+        ram
+    }
+
+    /// The opposite of [`Mapper::read_ram`]. Anything past the end of `ram` is left alone
+    pub fn write_ram(&self, memory: &mut impl Memory, ram: &[u8]) {
+        for (bank, chunk) in ram.chunks(RAM_BANK_LEN).take(self.ram_len.div_ceil(RAM_BANK_LEN)).enumerate() {
+            self.map_ram(memory, bank as u8);
+
+            for (addr, &byte) in (0xA000..).zip(chunk) {
+                memory.set(addr, byte);
+            }
+        }
+
+        self.restore(memory);
+    }
+
+    /// Sets the clock, writing the current registers into the core's clock too.
+    /// The core only latches from its current registers, so its latched set is left as it is
+    pub fn write_clock_registers(&mut self, memory: &mut impl Memory, clock: Clock) {
+        if !self.rtc {
+            return;
+        }
+
+        self.clock = clock;
+        memory.set(0x0000, 0x0A);
+
+        for (register, value) in (RTC_REGISTERS..).zip(clock.current) {
+            memory.set(0x4000, register);
+            memory.set(0xA000, value);
+        }
+
+        self.restore(memory);
+    }
+
+    /// Enables RAM and maps `bank` into $A000-$BFFF
+    fn map_ram(&self, memory: &mut impl Memory, bank: u8) {
+        memory.set(0x0000, 0x0A);
+
+        match self.kind {
+            Kind::Mbc1 => {
+                // RAM banking only happens in mode 1
+                memory.set(0x6000, 0x01);
+                memory.set(0x4000, bank);
+            },
+            Kind::Mbc3 | Kind::Mbc5 => memory.set(0x4000, bank),
+            Kind::None | Kind::Mbc2 => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(cart_type: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x150];
+        rom[CART_TYPE] = cart_type;
+        rom[RAM_SIZE] = ram_size;
+        rom
+    }
+
+    #[test]
+    fn header() {
+        let mapper = Mapper::new(&rom(0x10, 0x03));
+        assert_eq!(mapper.kind, Kind::Mbc3);
+        assert_eq!(mapper.ram_len, 0x8000);
+        assert!(mapper.battery && mapper.rtc);
+
+        let mapper = Mapper::new(&rom(0x06, 0x00));
+        assert_eq!(mapper.kind, Kind::Mbc2);
+        assert_eq!(mapper.ram_len, MBC2_RAM_LEN);
+        assert!(mapper.battery && !mapper.rtc);
+
+        assert_eq!(Mapper::new(&rom(0x00, 0x00)).kind, Kind::None);
+    }
+
+    #[test]
+    fn mbc1_banks() {
+        let mut mapper = Mapper::new(&rom(0x01, 0x00));
+        assert_eq!(mapper.rom_bank(), 1);
+
+        // bank 0 can't be mapped high, so it reads as 1
+        mapper.write(0x2000, 0x00);
+        assert_eq!(mapper.rom_bank(), 1);
+
+        mapper.write(0x3FFF, 0x05);
+        mapper.write(0x4000, 0x01);
+        assert_eq!(mapper.rom_bank(), 0x25);
+    }
+
+    #[test]
+    fn mbc5_banks() {
+        let mut mapper = Mapper::new(&rom(0x19, 0x00));
+        mapper.write(0x2000, 0x00);
+        assert_eq!(mapper.rom_bank(), 0);
+
+        mapper.write(0x3000, 0x01);
+        mapper.write(0x2000, 0x10);
+        assert_eq!(mapper.rom_bank(), 0x110);
+    }
+
+    #[test]
+    fn mbc2_register_select() {
+        let mut mapper = Mapper::new(&rom(0x05, 0x00));
+        mapper.write(0x0000, 0x0A);
+        mapper.write(0x2100, 0x03);
+
+        assert!(mapper.ram_enabled());
+        assert_eq!(mapper.rom_bank(), 3);
+    }
+
+    #[test]
+    fn ram_offsets() {
+        let mut mapper = Mapper::new(&rom(0x13, 0x03));
+        assert_eq!(mapper.ram_offset(0xA000), None);
+
+        mapper.write(0x0000, 0x0A);
+        mapper.write(0x4000, 0x02);
+        assert_eq!(mapper.ram_offset(0xA010), Some(2 * RAM_BANK_LEN + 0x10));
+        assert_eq!(mapper.ram_offset(0xC000), None);
+
+        // a clock register rather than RAM
+        mapper.write(0x4000, 0x08);
+        assert_eq!(mapper.ram_offset(0xA000), None);
+
+        let mut mapper = Mapper::new(&rom(0x06, 0x00));
+        mapper.write(0x0000, 0x0A);
+        assert_eq!(mapper.ram_offset(0xA205), Some(0x05));
+    }
+
+    #[test]
+    fn clock() {
+        let mut mapper = Mapper::new(&rom(0x10, 0x03));
+        mapper.write(0x0000, 0x0A);
+        mapper.write(0x4000, 0x0A);
+        mapper.write(0xA000, 23);
+        mapper.write(0x4000, 0x09);
+        mapper.write(0xA000, 59);
+        mapper.write(0x4000, 0x08);
+        mapper.write(0xA000, 59);
+
+        mapper.tick(CYCLES_PER_SECOND * 61);
+        assert_eq!(mapper.clock().unwrap().current, [0, 1, 0, 1, 0]);
+        assert_eq!(mapper.clock().unwrap().latched, [0; 5]);
+
+        mapper.write(0x6000, 0x00);
+        mapper.write(0x6000, 0x01);
+        mapper.tick(CYCLES_PER_SECOND);
+        assert_eq!(mapper.clock().unwrap().latched, [0, 1, 0, 1, 0]);
+        assert_eq!(mapper.clock().unwrap().current, [1, 1, 0, 1, 0]);
+
+        // writing 1 again without a 0 first doesn't latch
+        mapper.write(0x6000, 0x01);
+        assert_eq!(mapper.clock().unwrap().latched, [0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn clock_halts_and_carries() {
+        let mut clock = Clock::new([0, 0, 0, 0, HALT], [0; 5]);
+        clock.advance(100);
+        assert_eq!(clock.current, [0, 0, 0, 0, HALT]);
+
+        let mut clock = Clock::new([59, 59, 23, 0xFF, DAY_HIGH], [0; 5]);
+        clock.advance(1);
+        assert_eq!(clock.current, [0, 0, 0, 0, DAY_CARRY]);
+    }
+
+    #[test]
+    fn bank_offsets() {
+        let mapper = Mapper::new(&rom(0x1B, 0x03));
+        assert_eq!(mapper.bank_offset(2, 0xA010), Some(2 * RAM_BANK_LEN + 0x10));
+        // past the end of the RAM it wraps around
+        assert_eq!(mapper.bank_offset(5, 0xA010), Some(RAM_BANK_LEN + 0x10));
+        assert_eq!(mapper.bank_offset(0, 0xC000), None);
+    }
+
+    #[test]
+    fn ignores_ram_writes() {
+        let mut mapper = Mapper::new(&rom(0x1B, 0x03));
+        let registers = mapper.registers();
+        mapper.write(0xA000, 0x0A);
+        mapper.write(0xC000, 0x05);
+
+        assert_eq!(mapper.registers(), registers);
+        assert!(!mapper.ram_enabled());
+    }
+}
@@ -0,0 +1,392 @@
+use std::{fmt::Display, path::{Path, PathBuf}};
+
+use gbc::Button;
+
+const MAGIC: &[u8; 4] = b"BWMV";
+/// Bump this whenever the layout changes
+const VERSION: u16 = 1;
+const EVENT_LEN: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieEvent {
+    /// Emulated T-cycles since power-on
+    pub cycle: u64,
+    pub button: Button,
+    pub pressed: bool,
+}
+
+/// Where a recording begins
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieStart {
+    PowerOn,
+    /// From wherever the emu is right now, which gets embedded as a save state
+    Now,
+}
+
+#[derive(Clone, Debug)]
+pub struct Movie {
+    pub rom_checksum: u16,
+    pub rerecords: u32,
+    /// Encoded save state to start from, or None to start from power-on
+    pub start_state: Option<Vec<u8>>,
+    pub events: Vec<MovieEvent>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieMode {
+    Recording,
+    Playing,
+}
+
+/// What the UI gets told about the active movie
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieStatus {
+    pub mode: MovieMode,
+    pub read_only: bool,
+    pub rerecords: u32,
+    pub events: usize,
+    /// Whether playback has gone past the last event
+    pub finished: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    WrongRom { expected: u16, found: u16 },
+    Truncated,
+    BadButton(u8),
+}
+
+impl Display for MovieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a movie"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported movie version {version}"),
+            Self::WrongRom { expected, found } => write!(f, "movie is for a different rom (checksum {found:#06X}, expected {expected:#06X})"),
+            Self::Truncated => write!(f, "movie is truncated"),
+            Self::BadButton(button) => write!(f, "movie contains an unknown button {button}"),
+        }
+    }
+}
+
+/// A movie being recorded or played back by the runner
+#[derive(Clone, Debug)]
+pub struct MovieSession {
+    pub movie: Movie,
+    pub mode: MovieMode,
+    pub read_only: bool,
+    /// Index of the next event to play back
+    next: usize,
+    /// Whether there's anything that hasn't been written to disk yet
+    pub dirty: bool,
+}
+
+impl MovieSession {
+    pub fn recording(movie: Movie) -> Self {
+        Self {
+            movie,
+            mode: MovieMode::Recording,
+            read_only: false,
+            next: 0,
+            dirty: true,
+        }
+    }
+
+    pub fn playing(movie: Movie) -> Self {
+        Self {
+            movie,
+            mode: MovieMode::Playing,
+            read_only: true,
+            next: 0,
+            dirty: false,
+        }
+    }
+
+    pub fn status(&self) -> MovieStatus {
+        MovieStatus {
+            mode: self.mode,
+            read_only: self.read_only,
+            rerecords: self.movie.rerecords,
+            events: self.movie.events.len(),
+            finished: self.mode == MovieMode::Playing && self.next >= self.movie.events.len(),
+        }
+    }
+
+    /// Returns the next event to apply if it's due by `cycle`
+    pub fn due(&mut self, cycle: u64) -> Option<MovieEvent> {
+        if self.mode != MovieMode::Playing {
+            return None;
+        }
+
+        let event = *self.movie.events.get(self.next)?;
+        if event.cycle > cycle {
+            return None;
+        }
+
+        self.next += 1;
+        Some(event)
+    }
+
+    /// Decides what happens to input from the user. Returns whether it should reach the emu
+    pub fn user_input(&mut self, cycle: u64, button: Button, pressed: bool) -> bool {
+        match self.mode {
+            MovieMode::Playing if self.read_only => false,
+            MovieMode::Playing => {
+                // input in read-write mode takes over from the movie at this point
+                self.rerecord(cycle);
+                self.record(cycle, button, pressed);
+                true
+            },
+            MovieMode::Recording => {
+                self.record(cycle, button, pressed);
+                true
+            },
+        }
+    }
+
+    /// Called when the emu jumps to `cycle`, through a save state or rewinding
+    pub fn seek(&mut self, cycle: u64) {
+        if self.read_only {
+            self.mode = MovieMode::Playing;
+            self.next = self.movie.events.partition_point(|event| event.cycle < cycle);
+        } else {
+            self.rerecord(cycle);
+        }
+    }
+
+    /// Throws away everything from `cycle` on and starts recording from there
+    fn rerecord(&mut self, cycle: u64) {
+        let keep = self.movie.events.partition_point(|event| event.cycle < cycle);
+        self.movie.events.truncate(keep);
+        self.movie.rerecords += 1;
+        self.mode = MovieMode::Recording;
+        self.next = keep;
+        self.dirty = true;
+    }
+
+    fn record(&mut self, cycle: u64, button: Button, pressed: bool) {
+        self.movie.events.push(MovieEvent { cycle, button, pressed });
+        self.next = self.movie.events.len();
+        self.dirty = true;
+    }
+}
+
+/// Movies live next to the rom, as `<rom>.bwm`
+pub fn path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("bwm")
+}
+
+/// Layout, all LE:
+/// ```text
+/// 0..4    magic "BWMV"
+/// 4..6    format version
+/// 6..8    rom global checksum
+/// 8..12   rerecord count
+/// 12..16  start state length, 0 for power-on
+/// ..      start state, as written by savestate::encode
+/// ..+4    event count
+/// ..      events: cycle (u64), button (u8), pressed (u8)
+/// ```
+pub fn encode(movie: &Movie) -> Vec<u8> {
+    let start_state = movie.start_state.as_deref().unwrap_or(&[]);
+    let mut out = Vec::with_capacity(20 + start_state.len() + movie.events.len() * EVENT_LEN);
+
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&movie.rom_checksum.to_le_bytes());
+    out.extend_from_slice(&movie.rerecords.to_le_bytes());
+    out.extend_from_slice(&(start_state.len() as u32).to_le_bytes());
+    out.extend_from_slice(start_state);
+    out.extend_from_slice(&(movie.events.len() as u32).to_le_bytes());
+
+    for event in &movie.events {
+        out.extend_from_slice(&event.cycle.to_le_bytes());
+        out.push(button_to_byte(event.button));
+        out.push(event.pressed as u8);
+    }
+
+    out
+}
+
+pub fn decode(rom_checksum: u16, data: &[u8]) -> Result<Movie, MovieError> {
+    let mut reader = Reader { data, pos: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err(MovieError::BadMagic);
+    }
+
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(MovieError::UnsupportedVersion(version));
+    }
+
+    let found = reader.u16()?;
+    if found != rom_checksum {
+        return Err(MovieError::WrongRom { expected: rom_checksum, found });
+    }
+
+    let rerecords = reader.u32()?;
+    let start_len = reader.u32()? as usize;
+    let start_state = if start_len > 0 {
+        Some(reader.take(start_len)?.to_vec())
+    } else {
+        None
+    };
+
+    let count = reader.u32()? as usize;
+    let mut events = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let event = reader.take(EVENT_LEN)?;
+
+        events.push(MovieEvent {
+            cycle: u64::from_le_bytes(event[0..8].try_into().unwrap()),
+            button: byte_to_button(event[8])?,
+            pressed: event[9] != 0,
+        });
+    }
+
+    Ok(Movie {
+        rom_checksum,
+        rerecords,
+        start_state,
+        events,
+    })
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MovieError> {
+        let out = self.data.get(self.pos..self.pos + len).ok_or(MovieError::Truncated)?;
+        self.pos += len;
+        Ok(out)
+    }
+
+    fn u16(&mut self) -> Result<u16, MovieError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, MovieError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn button_to_byte(button: Button) -> u8 {
+    match button {
+        Button::Up => 0,
+        Button::Down => 1,
+        Button::Left => 2,
+        Button::Right => 3,
+        Button::A => 4,
+        Button::B => 5,
+        Button::Start => 6,
+        Button::Select => 7,
+    }
+}
+
+fn byte_to_button(byte: u8) -> Result<Button, MovieError> {
+    Ok(match byte {
+        0 => Button::Up,
+        1 => Button::Down,
+        2 => Button::Left,
+        3 => Button::Right,
+        4 => Button::A,
+        5 => Button::B,
+        6 => Button::Start,
+        7 => Button::Select,
+        byte => return Err(MovieError::BadButton(byte)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie() -> Movie {
+        Movie {
+            rom_checksum: 0xBEEF,
+            rerecords: 3,
+            start_state: Some(vec![1, 2, 3]),
+            events: vec![
+                MovieEvent { cycle: 100, button: Button::A, pressed: true },
+                MovieEvent { cycle: 200, button: Button::A, pressed: false },
+                MovieEvent { cycle: 300, button: Button::Select, pressed: true },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let decoded = decode(0xBEEF, &encode(&movie())).unwrap();
+
+        assert_eq!(decoded.rerecords, 3);
+        assert_eq!(decoded.start_state, Some(vec![1, 2, 3]));
+        assert_eq!(decoded.events, movie().events);
+    }
+
+    #[test]
+    fn power_on_round_trip() {
+        let movie = Movie { start_state: None, ..movie() };
+        assert_eq!(decode(0xBEEF, &encode(&movie)).unwrap().start_state, None);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let data = encode(&movie());
+
+        assert!(matches!(decode(0x1234, &data), Err(MovieError::WrongRom { expected: 0x1234, found: 0xBEEF })));
+        assert!(matches!(decode(0xBEEF, &data[..data.len() - 1]), Err(MovieError::Truncated)));
+        assert!(matches!(decode(0xBEEF, b"nope"), Err(MovieError::BadMagic)));
+
+        let mut bad_button = data;
+        let last = bad_button.len() - 2;
+        bad_button[last] = 99;
+        assert!(matches!(decode(0xBEEF, &bad_button), Err(MovieError::BadButton(99))));
+    }
+
+    #[test]
+    fn plays_back_in_order() {
+        let mut session = MovieSession::playing(movie());
+
+        assert_eq!(session.due(99), None);
+        assert_eq!(session.due(250).map(|event| event.cycle), Some(100));
+        assert_eq!(session.due(250).map(|event| event.cycle), Some(200));
+        assert_eq!(session.due(250), None);
+        assert!(!session.status().finished);
+
+        assert!(session.due(300).is_some());
+        assert!(session.status().finished);
+    }
+
+    #[test]
+    fn read_only_blocks_input() {
+        let mut session = MovieSession::playing(movie());
+        assert!(!session.user_input(150, Button::B, true));
+        assert_eq!(session.movie.events.len(), 3);
+    }
+
+    #[test]
+    fn read_write_input_rerecords() {
+        let mut session = MovieSession::playing(movie());
+        session.read_only = false;
+
+        assert!(session.user_input(150, Button::B, true));
+        assert_eq!(session.mode, MovieMode::Recording);
+        assert_eq!(session.movie.rerecords, 4);
+        assert_eq!(session.movie.events.iter().map(|event| event.cycle).collect::<Vec<_>>(), [100, 150]);
+    }
+
+    #[test]
+    fn read_only_seek() {
+        let mut session = MovieSession::playing(movie());
+        session.seek(200);
+
+        assert_eq!(session.due(1000).map(|event| event.cycle), Some(200));
+        assert_eq!(session.movie.rerecords, 3);
+    }
+}
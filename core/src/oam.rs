@@ -0,0 +1,171 @@
+//! Sprites out of OAM at $FE00, and which of them the PPU's per-line limit drops.
+
+use std::ops::Range;
+
+use crate::runner::HEIGHT;
+
+pub const OAM: usize = 0xFE00;
+pub const SPRITES: usize = 40;
+/// Sprites the PPU picks per line, in OAM order. Any more on the same line aren't drawn
+pub const PER_LINE: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub index: usize,
+    /// Screen Y + 16, so 0 is fully above the screen
+    pub y: u8,
+    /// Screen X + 8, so 0 is fully left of the screen
+    pub x: u8,
+    pub tile: u8,
+    pub flags: u8,
+}
+
+impl Sprite {
+    pub fn screen_pos(&self) -> (i16, i16) {
+        (self.x as i16 - 8, self.y as i16 - 16)
+    }
+
+    /// Screen lines it covers, which can be none
+    pub fn lines(&self, tall: bool) -> Range<i16> {
+        let top = self.y as i16 - 16;
+        top..top + if tall { 16 } else { 8 }
+    }
+
+    /// OBP0 or OBP1, on DMG
+    pub fn dmg_palette(&self) -> usize {
+        (self.flags >> 4 & 1) as usize
+    }
+
+    pub fn cgb_palette(&self) -> usize {
+        (self.flags & 0b111) as usize
+    }
+
+    pub fn bank(&self) -> usize {
+        (self.flags >> 3 & 1) as usize
+    }
+
+    pub fn flip(&self) -> (bool, bool) {
+        (self.flags & 0x20 != 0, self.flags & 0x40 != 0)
+    }
+
+    /// Whether BG colors 1-3 draw over it
+    pub fn behind_bg(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    /// Tiles from top to bottom, with 8x16 sprites ignoring bit 0 and swapping halves when Y flipped
+    pub fn tiles(&self, tall: bool) -> Vec<usize> {
+        if !tall {
+            return vec![self.tile as usize];
+        }
+
+        let top = (self.tile & 0xFE) as usize;
+        match self.flip().1 {
+            false => vec![top, top + 1],
+            true => vec![top + 1, top],
+        }
+    }
+}
+
+pub fn sprites(memory: &[u8]) -> Vec<Sprite> {
+    (0..SPRITES)
+        .map(|index| {
+            let byte = |offset: usize| memory.get(OAM + index * 4 + offset).copied().unwrap_or(0);
+
+            Sprite {
+                index,
+                y: byte(0),
+                x: byte(1),
+                tile: byte(2),
+                flags: byte(3),
+            }
+        })
+        .collect()
+}
+
+/// How many lines each sprite gets dropped on for being past the limit. Only Y counts towards it,
+/// so sprites hidden off the side of the screen still use up a slot
+pub fn dropped_lines(sprites: &[Sprite], tall: bool) -> Vec<u32> {
+    let mut dropped = vec![0; sprites.len()];
+
+    for line in 0..HEIGHT as i16 {
+        let on_line = sprites.iter().enumerate().filter(|(_, sprite)| sprite.lines(tall).contains(&line));
+
+        for (i, _) in on_line.skip(PER_LINE) {
+            dropped[i] += 1;
+        }
+    }
+
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite(index: usize, y: u8) -> Sprite {
+        Sprite { index, y, x: 8, tile: 0, flags: 0 }
+    }
+
+    #[test]
+    fn limit_drops_later_sprites() {
+        let sprites = (0..12).map(|i| sprite(i, 16)).collect::<Vec<_>>();
+        let dropped = dropped_lines(&sprites, false);
+
+        assert_eq!(&dropped[..PER_LINE], &[0; PER_LINE]);
+        assert_eq!(&dropped[PER_LINE..], &[8, 8]);
+    }
+
+    #[test]
+    fn partial_overlap() {
+        let mut sprites = (0..10).map(|i| sprite(i, 16)).collect::<Vec<_>>();
+        // shares 4 lines with the rest, and only with 8x16 does it cover them all
+        sprites.push(sprite(10, 20));
+
+        assert_eq!(dropped_lines(&sprites, false)[10], 4);
+        assert_eq!(dropped_lines(&sprites, true)[10], 12);
+    }
+
+    #[test]
+    fn off_screen() {
+        // hidden off the side still counts, but above the screen doesn't
+        let mut sprites = (0..10).map(|i| Sprite { x: 0, ..sprite(i, 16) }).collect::<Vec<_>>();
+        sprites.push(sprite(10, 16));
+        sprites.push(sprite(11, 0));
+
+        let dropped = dropped_lines(&sprites, false);
+        assert_eq!((dropped[10], dropped[11]), (8, 0));
+    }
+
+    #[test]
+    fn tall_tiles() {
+        let sprite = Sprite { tile: 0x05, ..sprite(0, 16) };
+        assert_eq!(sprite.tiles(false), vec![0x05]);
+        assert_eq!(sprite.tiles(true), vec![0x04, 0x05]);
+
+        let flipped = Sprite { flags: 0x40, ..sprite };
+        assert_eq!(flipped.tiles(true), vec![0x05, 0x04]);
+    }
+
+    #[test]
+    fn parse() {
+        let mut memory = vec![0; 0x10000];
+        memory[OAM + 4..OAM + 8].copy_from_slice(&[0x20, 0x30, 0x42, 0x90]);
+
+        let sprites = sprites(&memory);
+        assert_eq!(sprites.len(), SPRITES);
+        assert_eq!(sprites[1], Sprite { index: 1, y: 0x20, x: 0x30, tile: 0x42, flags: 0x90 });
+        assert_eq!(sprites[1].screen_pos(), (0x28, 0x10));
+        assert_eq!(sprites[1].dmg_palette(), 1);
+        assert!(sprites[1].behind_bg());
+    }
+
+    #[test]
+    fn cgb_flags() {
+        // palette 5 from bank 1, with the DMG's OBP1 bit set too, which the CGB ignores
+        let sprite = Sprite { flags: 0x1D, ..sprite(0, 16) };
+        assert_eq!(sprite.cgb_palette(), 5);
+        assert_eq!(sprite.bank(), 1);
+        assert_eq!(sprite.dmg_palette(), 1);
+    }
+}
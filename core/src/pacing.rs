@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+use gbc::{memory::Memory, CpuError, CpuStatus, Gbc, Mmu};
+
+/// T-cycles per second
+pub const CLOCK_SPEED: f64 = 4_194_304.0;
+/// T-cycles per frame
+pub const CYCLES_PER_FRAME: u64 = 70224;
+
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 8.0;
+/// Speed used while slow motion is on, regardless of the multiplier
+pub const SLOW_MOTION_SPEED: f64 = 0.1;
+
+/// CGB speed switch, with the current speed in bit 7
+const KEY1: u16 = 0xFF4D;
+
+/// If emulation falls this far behind (e.g. the host hitched), stop trying to catch up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Keeps emulated time in step with wall time.
+///
+/// The runner reports the cycles it executed through [`Pacer::advance`], and once a frame's worth
+/// has gone by [`Pacer::deadline`] says how long to sleep for.
+#[derive(Clone, Copy, Debug)]
+pub struct Pacer {
+    speed: f64,
+    turbo: bool,
+    slow_motion: bool,
+    /// Wall time that `cycles` is measured from
+    start: Instant,
+    cycles: u64,
+    last_sync: u64,
+}
+
+impl Default for Pacer {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            turbo: false,
+            slow_motion: false,
+            start: Instant::now(),
+            cycles: 0,
+            last_sync: 0,
+        }
+    }
+}
+
+impl Pacer {
+    /// Starts measuring from now, so that time spent paused isn't made up for
+    pub fn reset(&mut self) {
+        self.start = Instant::now();
+        self.cycles = 0;
+        self.last_sync = 0;
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.reset();
+    }
+
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+        self.reset();
+    }
+
+    pub fn set_slow_motion(&mut self, slow_motion: bool) {
+        self.slow_motion = slow_motion;
+        self.reset();
+    }
+
+    pub fn turbo(&self) -> bool {
+        self.turbo
+    }
+
+    pub fn speed(&self) -> f64 {
+        if self.slow_motion {
+            SLOW_MOTION_SPEED
+        } else {
+            self.speed
+        }
+    }
+
+    pub fn advance(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    /// Returns when the runner should wake up if it's ahead of schedule
+    pub fn deadline(&mut self) -> Option<Instant> {
+        if self.turbo || self.cycles - self.last_sync < CYCLES_PER_FRAME {
+            return None;
+        }
+
+        self.last_sync = self.cycles;
+
+        let target = self.start + Duration::from_secs_f64(self.cycles as f64 / (CLOCK_SPEED * self.speed()));
+        let now = Instant::now();
+
+        if target > now {
+            Some(target)
+        } else {
+            if now - target > MAX_LAG {
+                self.reset();
+            }
+
+            None
+        }
+    }
+}
+
+/// T-cycles the last step took, counted at normal speed so they line up with PPU dots and wall time.
+/// DIV can't be used for this, since writes reset it and it stops during STOP
+pub fn step_cycles(emu: &Gbc<Mmu>, cpu_status: &Result<CpuStatus, CpuError>) -> u64 {
+    let cycles = match cpu_status {
+        Ok(CpuStatus::Run(cycles) | CpuStatus::Break(cycles, _)) => *cycles as u64,
+        _ => 0,
+    };
+
+    // in CGB double speed only the CPU goes faster
+    match emu.cpu.memory.load(KEY1).is_some_and(|key1| key1 & 0x80 != 0) {
+        true => cycles / 2,
+        false => cycles,
+    }
+}
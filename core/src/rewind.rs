@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+
+/// A snapshot is taken every this many frames. Rewinding plays them back at one per frame,
+/// so anything above 1 would skip frames
+pub const FRAMES_PER_SNAPSHOT: usize = 1;
+/// Each keyframe is followed by this many deltas before a new keyframe is taken
+const DELTAS_PER_KEYFRAME: usize = 60;
+/// How much compressed history is kept. How many snapshots that is depends on how much each frame
+/// changes and how big cartridge RAM is, but it's minutes of it for most games
+const MAX_BYTES: usize = 64 * 1024 * 1024;
+/// Zero runs shorter than this are cheaper to keep inside a literal run
+const MIN_ZERO_RUN: usize = 3;
+
+/// A keyframe and the snapshots taken after it, stored as deltas against it.
+/// Each snapshot carries the emulated cycle count it was taken at.
+struct Segment {
+    keyframe: (u64, Vec<u8>),
+    deltas: Vec<(u64, Vec<u8>)>,
+}
+
+impl Segment {
+    fn len(&self) -> usize {
+        self.deltas.len() + 1
+    }
+
+    fn bytes(&self) -> usize {
+        self.keyframe.1.len() + self.deltas.iter().map(|(_, delta)| delta.len()).sum::<usize>()
+    }
+}
+
+/// Ring buffer of compressed save states, newest at the back
+pub struct RewindBuffer {
+    segments: VecDeque<Segment>,
+    /// Uncompressed copy of the newest segment's keyframe, so deltas can be taken against it
+    keyframe: Vec<u8>,
+    len: usize,
+    /// Compressed size of everything in the buffer
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        Self {
+            segments: VecDeque::new(),
+            keyframe: Vec::new(),
+            len: 0,
+            bytes: 0,
+            max_bytes: MAX_BYTES,
+        }
+    }
+}
+
+impl RewindBuffer {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self {
+            max_bytes: self.max_bytes,
+            ..Default::default()
+        };
+    }
+
+    pub fn push(&mut self, state: &[u8], cycles: u64) {
+        let compressed = match self.segments.back_mut() {
+            Some(segment) if segment.deltas.len() < DELTAS_PER_KEYFRAME => {
+                let delta = compress(&self.keyframe, state);
+                let len = delta.len();
+                segment.deltas.push((cycles, delta));
+                len
+            },
+            _ => {
+                let keyframe = compress(&[], state);
+                let len = keyframe.len();
+                self.segments.push_back(Segment {
+                    keyframe: (cycles, keyframe),
+                    deltas: Vec::with_capacity(DELTAS_PER_KEYFRAME),
+                });
+                self.keyframe = state.to_vec();
+                len
+            }
+        };
+
+        self.len += 1;
+        self.bytes += compressed;
+
+        // whole segments are dropped so that no delta outlives its keyframe, but never the newest
+        while self.bytes > self.max_bytes && self.segments.len() > 1 {
+            let Some(oldest) = self.segments.pop_front() else { break };
+            self.len -= oldest.len();
+            self.bytes -= oldest.bytes();
+        }
+    }
+
+    /// Removes and returns the newest snapshot, along with the cycle count it was taken at
+    pub fn pop(&mut self) -> Option<(Vec<u8>, u64)> {
+        let segment = self.segments.back_mut()?;
+        self.len -= 1;
+
+        if let Some((cycles, delta)) = segment.deltas.pop() {
+            self.bytes -= delta.len();
+            return Some((decompress(&self.keyframe, &delta), cycles));
+        }
+
+        let keyframe = std::mem::take(&mut self.keyframe);
+        let (cycles, compressed) = self.segments.pop_back()?.keyframe;
+        self.bytes -= compressed.len();
+
+        if let Some(previous) = self.segments.back() {
+            self.keyframe = decompress(&[], &previous.keyframe.1);
+        }
+
+        Some((keyframe, cycles))
+    }
+}
+
+/// XORs `data` against `base` and run-length encodes the result.
+///
+/// The output is the length of `data`, followed by `(zero run, literal length, literals)` chunks,
+/// with every number stored as a LEB128 varint.
+fn compress(base: &[u8], data: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| data[i] ^ base.get(i).copied().unwrap_or(0);
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    write_varint(&mut out, data.len());
+
+    while i < data.len() {
+        let zeros_start = i;
+        while i < data.len() && xor(i) == 0 {
+            i += 1;
+        }
+        let zeros = i - zeros_start;
+
+        let literal_start = i;
+        while i < data.len() {
+            let run = (i..data.len().min(i + MIN_ZERO_RUN)).take_while(|&j| xor(j) == 0).count();
+            if run == MIN_ZERO_RUN || i + run == data.len() {
+                break;
+            }
+            i += run.max(1);
+        }
+
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, i - literal_start);
+        out.extend((literal_start..i).map(xor));
+    }
+
+    out
+}
+
+fn decompress(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos);
+    let mut out = base.to_vec();
+    out.resize(len, 0);
+
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literals = read_varint(delta, &mut pos);
+
+        for byte in &delta[pos..pos + literals] {
+            out[i] ^= byte;
+            i += 1;
+        }
+        pos += literals;
+    }
+
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(seed: u8) -> Vec<u8> {
+        (0..1000).map(|i| if i % 50 == 0 { seed.wrapping_add(i as u8) } else { 0 }).collect()
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        let base = state(1);
+        let data = state(2);
+
+        assert_eq!(decompress(&base, &compress(&base, &data)), data);
+        assert_eq!(decompress(&[], &compress(&[], &data)), data);
+        // a state that shrank
+        assert_eq!(decompress(&base, &compress(&base, &data[..10])), &data[..10]);
+    }
+
+    #[test]
+    fn unchanged_state_is_tiny() {
+        let data = state(3);
+        assert!(compress(&data, &data).len() < 8);
+    }
+
+    #[test]
+    fn varints() {
+        for value in [0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, usize::MAX >> 1] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+
+            let mut pos = 0;
+            assert_eq!(read_varint(&out, &mut pos), value);
+            assert_eq!(pos, out.len());
+        }
+    }
+
+    #[test]
+    fn pops_newest_first() {
+        let mut buffer = RewindBuffer::default();
+
+        // enough to span a few keyframes
+        for i in 0..DELTAS_PER_KEYFRAME as u8 * 3 {
+            buffer.push(&state(i), i as u64 * 100);
+        }
+
+        for i in (0..DELTAS_PER_KEYFRAME as u8 * 3).rev() {
+            assert_eq!(buffer.pop(), Some((state(i), i as u64 * 100)));
+        }
+
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn drops_oldest_segments() {
+        let mut buffer = RewindBuffer { max_bytes: 20_000, ..Default::default() };
+        let pushes = DELTAS_PER_KEYFRAME * 10;
+
+        for i in 0..pushes {
+            buffer.push(&state(i as u8), i as u64);
+        }
+
+        assert!(buffer.bytes <= buffer.max_bytes);
+        assert!(buffer.len() < pushes);
+        assert_eq!(buffer.pop(), Some((state(pushes as u8 - 1), pushes as u64 - 1)));
+
+        // popping everything leaves nothing counted
+        while buffer.pop().is_some() {}
+        assert_eq!(buffer.bytes, 0);
+    }
+}
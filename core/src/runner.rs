@@ -0,0 +1,1290 @@
+use std::{fmt::Display, path::{Path, PathBuf}, time::Duration};
+
+use gbc::{memory::Memory, CpuError, CpuEvent, CpuReg, CpuStatus, Gbc, Mmu};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{access::{self, Access}, apu::{self, Apu}, audio::{AudioSink, NullSink, Resampler}, battery, cheats::{self, Code}, comms::{EmuMsgIn, EmuMsgOut}, events::EventLogger, expr::Expr, mbc::Mapper, movie::{self, Movie, MovieSession, MovieStart}, pacing::{self, Pacer}, rewind::{self, RewindBuffer}, savestate::{self, Snapshot}, sink::FrameSink, state::StateDump, trace::{TraceFilter, Tracer}, vram};
+
+pub const WIDTH: usize = 160;
+pub const HEIGHT: usize = 144;
+/// Frames per second of the real hardware
+pub const FRAME_RATE: f64 = 59.7275;
+/// Audio is pulled from the APU four times a frame
+const AUDIO_PULL_CYCLES: u64 = pacing::CYCLES_PER_FRAME / 4;
+/// The CGB's WRAM bank register
+const SVBK: u16 = 0xFF70;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmuStatus {
+    Fresh,
+    Running,
+    #[default]
+    Stopped,
+    Break,
+    LoadingRom,
+    Stepping,
+    Rewinding,
+    FrameAdvancing,
+    /// Running until a step over, step out or run-to reaches its target
+    RunningTo,
+}
+
+impl Display for EmuStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Clone, Copy, Debug, )]
+pub enum EmuError {
+    Uninitialized,
+    What,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    A, B,
+    C, D,
+    H, L,
+    Zero,
+    Subtract,
+    HalfCarry,
+    Carry,
+    MemoryWrite(u16),
+    MemoryRead(u16),
+    /// Any read from `start..=end`
+    ReadRange(u16, u16),
+    /// Any write to `start..=end`
+    WriteRange(u16, u16),
+    /// A write to `addr` where `written & mask == value & mask`
+    WriteValue { addr: u16, value: u8, mask: u8 },
+    Pc(u16),
+}
+
+impl Breakpoint {
+    /// The CPU's own breakpoint for this. The CPU only watches writes to single addresses,
+    /// so reads, ranges and values are None and get checked by the runner instead
+    pub fn cpu_event(self) -> Option<CpuEvent> {
+        Some(match self {
+            Self::A => CpuEvent::Reg(CpuReg::A),
+            Self::B => CpuEvent::Reg(CpuReg::B),
+            Self::C => CpuEvent::Reg(CpuReg::C),
+            Self::D => CpuEvent::Reg(CpuReg::D),
+            Self::H => CpuEvent::Reg(CpuReg::H),
+            Self::L => CpuEvent::Reg(CpuReg::L),
+            Self::Zero => CpuEvent::Flag(gbc::CpuFlag::Zero),
+            Self::Subtract => CpuEvent::Flag(gbc::CpuFlag::Subtract),
+            Self::HalfCarry => CpuEvent::Flag(gbc::CpuFlag::HalfCarry),
+            Self::Carry => CpuEvent::Flag(gbc::CpuFlag::Carry),
+            Self::MemoryWrite(addr) => CpuEvent::MemoryWrite(addr),
+            Self::Pc(addr) => CpuEvent::Pc(addr),
+            Self::MemoryRead(_)
+            | Self::ReadRange(_, _)
+            | Self::WriteRange(_, _)
+            | Self::WriteValue { .. } => return None,
+        })
+    }
+
+    /// Whether a step that made `access` sets off one of the breakpoints the runner checks itself
+    fn hit_by(self, access: Access) -> bool {
+        match self {
+            Self::MemoryRead(addr) => access.read == Some(addr),
+            Self::ReadRange(start, end) => access.read.is_some_and(|read| (start..=end).contains(&read)),
+            Self::WriteRange(start, end) => access.writes.into_iter().flatten().any(|write| (start..=end).contains(&write.addr)),
+            Self::WriteValue { addr, value, mask } => access.writes.into_iter().flatten().any(|write| write.addr == addr && write.value & mask == value & mask),
+            _ => false,
+        }
+    }
+
+    /// The breakpoint that set `event`, if it's one we set
+    pub fn from_event(event: CpuEvent) -> Option<Self> {
+        Some(match event {
+            CpuEvent::Reg(CpuReg::A) => Self::A,
+            CpuEvent::Reg(CpuReg::B) => Self::B,
+            CpuEvent::Reg(CpuReg::C) => Self::C,
+            CpuEvent::Reg(CpuReg::D) => Self::D,
+            CpuEvent::Reg(CpuReg::H) => Self::H,
+            CpuEvent::Reg(CpuReg::L) => Self::L,
+            CpuEvent::Flag(gbc::CpuFlag::Zero) => Self::Zero,
+            CpuEvent::Flag(gbc::CpuFlag::Subtract) => Self::Subtract,
+            CpuEvent::Flag(gbc::CpuFlag::HalfCarry) => Self::HalfCarry,
+            CpuEvent::Flag(gbc::CpuFlag::Carry) => Self::Carry,
+            CpuEvent::MemoryWrite(addr) => Self::MemoryWrite(addr),
+            CpuEvent::Pc(addr) => Self::Pc(addr),
+            _ => return None,
+        })
+    }
+}
+
+/// A breakpoint that fires when an expression becomes true
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub id: usize,
+    pub expr: Expr,
+    /// How many times it has to become true before breaking. 0 and 1 both break the first time
+    pub hit_count: u32,
+}
+
+/// A condition as tracked by the runner
+#[derive(Clone, Debug)]
+struct ConditionState {
+    condition: Condition,
+    /// Only evaluated at this PC, if the expression pins one down
+    pc: Option<u16>,
+    hits: u32,
+    /// Hits are counted on the condition becoming true, so something like `ly == 144`
+    /// doesn't break again on the very next instruction after resuming
+    was_true: bool,
+}
+
+impl ConditionState {
+    fn new(condition: Condition) -> Self {
+        Self {
+            pc: condition.expr.pc(),
+            condition,
+            hits: 0,
+            was_true: false,
+        }
+    }
+}
+
+/// What an in-progress step over, step out or run-to is waiting for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RunTarget {
+    /// Stop at `addr` once the stack is back at or above `sp`, so recursive calls don't stop early
+    Address { addr: u16, sp: u16 },
+    /// Stop once a return pops the stack above `sp`
+    Return { sp: u16 },
+}
+
+pub struct Emu {
+    inner: Option<Gbc<Mmu>>,
+    receiver: mpsc::UnboundedReceiver<EmuMsgIn>,
+    sender: mpsc::UnboundedSender<EmuMsgOut>,
+    sink: Box<dyn FrameSink>,
+    status: EmuStatus,
+    steps_remaining: usize,
+    run_target: Option<RunTarget>,
+    /// PC breakpoints set by the user, which temporary ones mustn't unset
+    user_pc_breakpoints: Vec<u16>,
+    /// Breakpoints the CPU can't watch for, checked against each step's decoded accesses
+    memory_breakpoints: Vec<Breakpoint>,
+    /// The memory breakpoint the last step set off
+    memory_hit: Option<Breakpoint>,
+    conditions: Vec<ConditionState>,
+    rom: Vec<u8>,
+    rom_path: Option<PathBuf>,
+    rom_checksum: u16,
+    /// The cartridge's bank registers, since the core keeps its own to itself
+    mapper: Mapper,
+    /// Emulated T-cycles since power-on
+    cycles: u64,
+    movie: Option<MovieSession>,
+    /// Input received while paused, applied when emulation continues.
+    /// Presses are applied at the start of the next frame and releases at the end of it.
+    latched_presses: Vec<gbc::Button>,
+    latched_releases: Vec<gbc::Button>,
+    rewind: RewindBuffer,
+    frames: usize,
+    /// Cartridge RAM as of the last write to the .sav, for telling whether it's dirty
+    battery_ram: Vec<u8>,
+    /// Cartridge RAM as the game has it, kept up with the writes each step makes so that taking a
+    /// rewind snapshot every frame doesn't mean switching through the banks to read it
+    cart_ram: Vec<u8>,
+    /// Set once a movie swaps in cartridge RAM that didn't come from the .sav, like a power-on
+    /// recording's blank RAM, so it never gets written over the real save. Lasts until the next rom load
+    battery_tainted: bool,
+    pacer: Pacer,
+    apu: Apu,
+    audio: Box<dyn AudioSink>,
+    resampler: Resampler,
+    /// Resampled audio waiting to be pushed, kept around to avoid reallocating
+    audio_buf: Vec<[f32; 2]>,
+    /// Cycles since audio was last pulled from the APU
+    audio_cycles: u64,
+    volume: f32,
+    muted: bool,
+    trace: Option<Tracer>,
+    /// Memory held at a value, as (addr, bytes), rewritten every frame
+    freezes: Vec<(u16, Vec<u8>)>,
+    /// GameShark codes, rewritten every frame like freezes
+    ram_cheats: Vec<Code>,
+    /// Game Genie codes written into the loaded copy of the ROM, which `rom` itself never has
+    rom_patches: Vec<Code>,
+    events: Option<EventLogger>,
+    /// The runner this one replaces, which has to finish writing the .sav before it's read again
+    previous: Option<JoinHandle<()>>,
+}
+
+impl Emu {
+    pub fn new(receiver: mpsc::UnboundedReceiver<EmuMsgIn>, sender: mpsc::UnboundedSender<EmuMsgOut>, sink: impl FrameSink) -> Self {
+        let inner = None;
+
+        Self {
+            inner,
+            receiver,
+            sender,
+            sink: Box::new(sink),
+            status: Default::default(),
+            steps_remaining: 0,
+            run_target: None,
+            user_pc_breakpoints: Vec::new(),
+            memory_breakpoints: Vec::new(),
+            memory_hit: None,
+            conditions: Vec::new(),
+            rom: Vec::new(),
+            rom_path: None,
+            rom_checksum: 0,
+            mapper: Default::default(),
+            cycles: 0,
+            movie: None,
+            latched_presses: Vec::new(),
+            latched_releases: Vec::new(),
+            rewind: Default::default(),
+            frames: 0,
+            battery_ram: Vec::new(),
+            cart_ram: Vec::new(),
+            battery_tainted: false,
+            pacer: Default::default(),
+            apu: Default::default(),
+            audio: Box::new(NullSink::default()),
+            resampler: Resampler::new(apu::SAMPLE_RATE, NullSink::default().sample_rate),
+            audio_buf: Vec::new(),
+            audio_cycles: 0,
+            volume: 1.0,
+            muted: false,
+            trace: None,
+            freezes: Vec::new(),
+            ram_cheats: Vec::new(),
+            rom_patches: Vec::new(),
+            events: None,
+            previous: None,
+        }
+    }
+
+    /// Audio goes nowhere until this is called
+    pub fn set_audio_sink(&mut self, sink: impl AudioSink) {
+        self.resampler = Resampler::new(apu::SAMPLE_RATE, sink.sample_rate());
+        self.audio = Box::new(sink);
+    }
+
+    /// Traces every instruction from the start, for headless runs
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.trace = Some(tracer);
+    }
+
+    /// Holds off on loading the .sav until `previous` has shut down, for when this runner replaces it
+    pub fn after(&mut self, previous: JoinHandle<()>) {
+        self.previous = Some(previous);
+    }
+
+    /// Powers on with `rom`. Battery saves, states and movies live next to `rom_path`,
+    /// so a rom without one runs without them
+    pub fn init(&mut self, rom: &[u8], rom_path: Option<PathBuf>) {
+        let emu = Self::power_on(rom);
+        self.apu.sync(&emu.cpu.memory);
+        self.inner = Some(emu);
+        self.mapper = Mapper::new(rom);
+        self.rom = rom.to_vec();
+        self.rom_path = rom_path;
+        self.rom_checksum = savestate::rom_checksum(rom);
+    }
+
+    fn power_on(rom: &[u8]) -> Gbc<Mmu> {
+        let mbc = gbc::get_mbc(rom);
+        let mut emu = Gbc::new(mbc, false, true);
+        emu.load_rom(rom);
+        emu
+    }
+
+    pub fn run(mut self) -> Result<JoinHandle<()>, EmuError> {
+        if let Some(mut emu) = self.inner {
+            self.inner = None;
+
+            let task = tokio::spawn(async move {
+                if let Some(previous) = self.previous.take() {
+                    let _ = previous.await;
+                }
+                self.load_battery(&mut emu);
+                self.sync_cart_ram(&mut emu);
+
+                // self.set_status(EmuStatus::Break);
+                // emu.cpu.breakpoint_controls.set(CpuEvent::LdBb);
+                let mut buf: Option<EmuMsgIn> = None;
+                let mut status = EmuStatus::Running;
+                let mut old_status;
+                // what to go back to when rewinding stops
+                let mut rewind_return = status;
+                self.set_status(status);
+
+                loop {
+                    let msg = if let Some(msg) = buf {
+                        buf = None;
+                        Ok(msg)
+                    } else {
+                        self.receiver.try_recv()
+                    };
+                    
+                    old_status = status;
+
+                    match msg {
+                        Ok(msg) => {
+                            use EmuMsgIn::*;
+                            
+                            match msg {
+                                Exit => {
+                                    self.shutdown(&mut emu);
+                                    let _ = self.sender.send(EmuMsgOut::Exited);
+                                    return
+                                },
+                                Pause => {
+                                    self.clear_run_target(&mut emu);
+                                    status = EmuStatus::Stopped
+                                },
+                                Resume => {
+                                    // releases wait for the end of the first frame, so a tap made while paused still lands
+                                    self.apply_latched_presses(&mut emu);
+                                    status = EmuStatus::Running
+                                },
+                                FrameAdvance => {
+                                    self.apply_latched_presses(&mut emu);
+                                    status = EmuStatus::FrameAdvancing;
+                                },
+                                TraceStart { path, filter, pin_ly } => {
+                                    self.start_trace(&path, filter, pin_ly);
+                                },
+                                TraceStop => {
+                                    self.stop_trace();
+                                },
+                                WriteMemory { addr, bytes } => {
+                                    for (i, byte) in bytes.into_iter().enumerate() {
+                                        emu.cpu.memory.set(addr.wrapping_add(i as u16), byte);
+                                        self.mapper.write(addr.wrapping_add(i as u16), byte);
+                                        self.apu.write(addr.wrapping_add(i as u16), byte);
+                                        self.track_cart_ram(addr.wrapping_add(i as u16), byte);
+                                    }
+
+                                    // a running emu sends its state every frame anyway
+                                    if matches!(status, EmuStatus::Stopped | EmuStatus::Break) {
+                                        self.dump_state(&mut emu);
+                                    }
+                                },
+                                Freeze { addr, bytes } => {
+                                    self.freezes.retain(|&(frozen, _)| frozen != addr);
+                                    self.freezes.push((addr, bytes));
+                                    self.apply_freezes(&mut emu);
+                                },
+                                Unfreeze(addr) => {
+                                    self.freezes.retain(|&(frozen, _)| frozen != addr);
+                                },
+                                SetCheats(codes) => {
+                                    self.set_cheats(&mut emu, codes);
+                                },
+                                LogEvents(on) => {
+                                    self.flush_events();
+                                    self.events = on.then(EventLogger::default);
+                                },
+                                SetRegisters(regs) => {
+                                    emu.cpu.regs = regs;
+
+                                    if matches!(status, EmuStatus::Stopped | EmuStatus::Break) {
+                                        self.dump_state(&mut emu);
+                                    }
+                                },
+                                LoadRom => {
+                                    // this instance should be dropped and a new instance should replace it
+                                    self.shutdown(&mut emu);
+                                    return
+                                },
+                                Step(steps) => {
+                                    self.apply_latched_presses(&mut emu);
+                                    self.steps_remaining = steps;
+                                    status = EmuStatus::Stepping;
+                                },
+                                StepOver => {
+                                    self.apply_latched_presses(&mut emu);
+                                    let pc = emu.cpu.regs.pc;
+                                    let opcode = emu.cpu.memory.load(pc).unwrap_or(0);
+
+                                    if let Some(len) = call_len(opcode) {
+                                        let target = RunTarget::Address { addr: pc.wrapping_add(len), sp: emu.cpu.regs.sp };
+                                        self.set_run_target(&mut emu, target);
+                                        status = EmuStatus::RunningTo;
+                                    } else {
+                                        self.steps_remaining = 1;
+                                        status = EmuStatus::Stepping;
+                                    }
+                                },
+                                StepOut => {
+                                    self.apply_latched_presses(&mut emu);
+                                    let sp = emu.cpu.regs.sp;
+                                    self.set_run_target(&mut emu, RunTarget::Return { sp });
+                                    status = EmuStatus::RunningTo;
+                                },
+                                RunTo(addr) => {
+                                    self.apply_latched_presses(&mut emu);
+                                    self.set_run_target(&mut emu, RunTarget::Address { addr, sp: 0 });
+                                    status = EmuStatus::RunningTo;
+                                },
+                                SetBreakpoint(breakpoint) => {
+                                    if let Breakpoint::Pc(addr) = breakpoint {
+                                        self.user_pc_breakpoints.push(addr);
+                                    }
+
+                                    match breakpoint.cpu_event() {
+                                        Some(event) => emu.cpu.breakpoint_controls.set(event),
+                                        None => self.memory_breakpoints.push(breakpoint),
+                                    }
+                                },
+                                UnsetBreakpoint(breakpoint) => {
+                                    if let Breakpoint::Pc(addr) = breakpoint {
+                                        self.user_pc_breakpoints.retain(|&a| a != addr);
+                                    }
+
+                                    match breakpoint.cpu_event() {
+                                        Some(event) => emu.cpu.breakpoint_controls.unset(event),
+                                        None => self.memory_breakpoints.retain(|&b| b != breakpoint),
+                                    }
+                                },
+                                SetCondition(condition) => {
+                                    self.conditions.retain(|state| state.condition.id != condition.id);
+                                    self.conditions.push(ConditionState::new(condition));
+                                },
+                                UnsetCondition(id) => {
+                                    self.conditions.retain(|state| state.condition.id != id);
+                                },
+                                SetSpeed(speed) => {
+                                    self.pacer.set_speed(speed);
+                                },
+                                Turbo(turbo) => {
+                                    self.pacer.set_turbo(turbo);
+                                },
+                                SlowMotion(slow_motion) => {
+                                    self.pacer.set_slow_motion(slow_motion);
+                                },
+                                SetVolume(volume) => {
+                                    self.volume = volume.clamp(0.0, 1.0);
+                                },
+                                SetMuted(muted) => {
+                                    self.muted = muted;
+                                },
+                                ButtonPressed(button) => {
+                                    if matches!(status, EmuStatus::Stopped | EmuStatus::Break) {
+                                        self.latched_presses.push(button);
+                                    } else if self.user_input(button, true) {
+                                        emu.press_button(button);
+                                    }
+                                },
+                                ButtonReleased(button) => {
+                                    if matches!(status, EmuStatus::Stopped | EmuStatus::Break) {
+                                        self.latched_releases.push(button);
+                                    } else if self.user_input(button, false) {
+                                        emu.release_button(button);
+                                    }
+                                },
+                                SaveState(slot) => {
+                                    self.save_state(&mut emu, slot);
+                                },
+                                LoadState(slot) => {
+                                    if self.load_state(&mut emu, slot) {
+                                        self.present(&emu);
+                                        self.dump_state(&mut emu);
+                                    }
+                                },
+                                RewindStart => {
+                                    if status != EmuStatus::Rewinding {
+                                        rewind_return = status;
+                                        status = EmuStatus::Rewinding;
+
+                                        // the newest snapshot is where the emu already is, and redrawing from it would jump a frame ahead
+                                        self.rewind.pop();
+                                    }
+                                },
+                                RewindStop => {
+                                    if status == EmuStatus::Rewinding {
+                                        status = rewind_return;
+                                        self.seek_movie();
+                                    }
+                                },
+                                MovieRecord(start) => {
+                                    self.start_recording(&mut emu, start);
+                                    self.present(&emu);
+                                    self.dump_state(&mut emu);
+                                },
+                                MoviePlay => {
+                                    self.start_playback(&mut emu);
+                                    self.present(&emu);
+                                    self.dump_state(&mut emu);
+                                },
+                                MovieStop => {
+                                    self.stop_movie();
+                                },
+                                MovieReadOnly(read_only) => {
+                                    if let Some(ref mut movie) = self.movie {
+                                        movie.read_only = read_only;
+                                    }
+                                    self.send_movie_status();
+                                },
+                            }
+                        },
+                        Err(mpsc::error::TryRecvError::Empty) => {},
+                        Err(mpsc::error::TryRecvError::Disconnected) => {
+                            self.shutdown(&mut emu);
+                            return
+                        },
+                    }
+
+                    match status {
+                        EmuStatus::Running => {
+                            let (cpu_status, draw_ready) = self.step(&mut emu);
+
+                            if draw_ready {
+                                self.apply_latched_releases(&mut emu);
+                            }
+
+                            if self.should_break(&emu, &cpu_status) {
+                                status = EmuStatus::Break;
+                                self.dump_state(&mut emu);
+                            }
+
+                            if let Some(deadline) = self.pacer.deadline() {
+                                tokio::time::sleep_until(deadline.into()).await;
+                            }
+                        },
+                        EmuStatus::FrameAdvancing => {
+                            let (cpu_status, draw_ready) = self.step(&mut emu);
+
+                            if self.should_break(&emu, &cpu_status) {
+                                status = EmuStatus::Break;
+                                self.dump_state(&mut emu);
+                            } else if draw_ready {
+                                self.apply_latched_releases(&mut emu);
+                                status = EmuStatus::Stopped;
+                            }
+                        },
+                        EmuStatus::RunningTo => {
+                            let sp = emu.cpu.regs.sp;
+                            let opcode = emu.cpu.memory.load(emu.cpu.regs.pc).unwrap_or(0);
+                            let (cpu_status, _) = self.step(&mut emu);
+
+                            let reached = match self.run_target {
+                                Some(RunTarget::Address { addr, sp: min_sp }) => emu.cpu.regs.pc == addr && emu.cpu.regs.sp >= min_sp,
+                                // a taken return pops exactly 2 bytes, which sets it apart from a return that wasn't taken
+                                Some(RunTarget::Return { sp: start }) => is_return(opcode) && emu.cpu.regs.sp == sp.wrapping_add(2) && emu.cpu.regs.sp > start,
+                                None => true,
+                            };
+
+                            let condition_hit = self.check_conditions(&emu);
+
+                            if reached {
+                                self.clear_run_target(&mut emu);
+                                self.apply_latched_releases(&mut emu);
+                                status = EmuStatus::Stopped;
+                            } else if condition_hit || self.memory_hit.is_some() || matches!(cpu_status, Ok(CpuStatus::Break(_, _))) {
+                                // our own breakpoint firing in a deeper call isn't a reason to stop
+                                let own = !condition_hit && self.memory_hit.is_none() && matches!(self.run_target, Some(RunTarget::Address { addr, .. }) if addr == emu.cpu.regs.pc);
+
+                                if !own {
+                                    self.report_break(&cpu_status);
+                                    self.clear_run_target(&mut emu);
+                                    self.apply_latched_releases(&mut emu);
+                                    status = EmuStatus::Break;
+                                    self.dump_state(&mut emu);
+                                }
+                            }
+                        },
+                        EmuStatus::Stepping => {
+                            let _ = self.step(&mut emu);
+
+                            self.steps_remaining -= 1;
+                            if self.steps_remaining == 0 {
+                                // input latched before stepping is let go of once it stops, like at the end of a frame advance
+                                self.apply_latched_releases(&mut emu);
+                                status = EmuStatus::Stopped;
+                            }
+                        },
+                        EmuStatus::Rewinding => {
+                            if let Some((snapshot, cycles)) = self.rewind.pop() {
+                                match Snapshot::from_bytes(&snapshot) {
+                                    Ok(snapshot) => {
+                                        snapshot.restore(&mut emu, &mut self.mapper);
+                                        self.apu.sync(&emu.cpu.memory);
+                                        self.cart_ram = snapshot.cart_ram;
+                                        self.cycles = cycles;
+
+                                        // snapshots don't keep the framebuffer, so the frame after this one gets run to draw it
+                                        self.redraw(&mut emu);
+                                    },
+                                    Err(err) => {
+                                        self.notify_error(format!("Couldn't rewind: {err}"));
+                                        self.rewind.clear();
+                                    }
+                                }
+
+                                self.present(&emu);
+                                self.dump_state(&mut emu);
+
+                                let frame_time = Duration::from_secs_f64(rewind::FRAMES_PER_SNAPSHOT as f64 / FRAME_RATE);
+                                tokio::time::sleep(frame_time).await;
+                            } else {
+                                // out of history, so hold on the oldest frame until the key is released
+                                buf = self.receiver.recv().await;
+                            }
+                        },
+                        EmuStatus::Break
+                        | EmuStatus::Stopped => {
+                            buf = self.receiver.recv().await;
+                        },
+                        _ => {}
+                    }
+                    
+                    if status != old_status {
+                        self.set_status(status);
+
+                        if status == EmuStatus::Running {
+                            self.pacer.reset();
+                        }
+                    }
+                }
+            });
+
+            return Ok(task)
+        }
+
+        Err(EmuError::Uninitialized)
+    }
+
+    /// Whether the last step hit a breakpoint, either in the CPU, in memory or one of the conditions
+    fn should_break(&mut self, emu: &Gbc<Mmu>, cpu_status: &Result<CpuStatus, CpuError>) -> bool {
+        // conditions are always checked so their hit counts stay right
+        let condition_hit = self.check_conditions(emu);
+        self.report_break(cpu_status);
+
+        condition_hit || self.memory_hit.is_some() || matches!(cpu_status, Ok(CpuStatus::Break(_, _)))
+    }
+
+    /// Lets the UI know which of its breakpoints stopped the CPU, so it can count hits
+    fn report_break(&self, cpu_status: &Result<CpuStatus, CpuError>) {
+        if let Ok(CpuStatus::Break(_, event)) = cpu_status {
+            if let Some(breakpoint) = Breakpoint::from_event(*event) {
+                let _ = self.sender.send(EmuMsgOut::BreakpointHit(breakpoint));
+            }
+        }
+
+        if let Some(breakpoint) = self.memory_hit {
+            let _ = self.sender.send(EmuMsgOut::BreakpointHit(breakpoint));
+        }
+    }
+
+    /// Evaluates every condition against the state the last step left behind, returning whether any should break
+    fn check_conditions(&mut self, emu: &Gbc<Mmu>) -> bool {
+        let mut hit = false;
+
+        for state in &mut self.conditions {
+            if state.pc.is_some_and(|pc| pc != emu.cpu.regs.pc) {
+                state.was_true = false;
+                continue;
+            }
+
+            let is_true = state.condition.expr.eval(emu) != 0;
+
+            if is_true && !state.was_true {
+                state.hits += 1;
+
+                if state.hits >= state.condition.hit_count {
+                    let _ = self.sender.send(EmuMsgOut::ConditionHit(state.condition.id));
+                    hit = true;
+                }
+            }
+
+            state.was_true = is_true;
+        }
+
+        hit
+    }
+
+    /// Runs a single instruction, returning whether it finished a frame along with the CPU status
+    fn step(&mut self, emu: &mut Gbc<Mmu>) -> (Result<CpuStatus, gbc::CpuError>, bool) {
+        self.apply_movie_input(emu);
+
+        if let Some(ref mut tracer) = self.trace {
+            if let Err(err) = tracer.trace(emu, self.frames, self.mapper.rom_bank()) {
+                self.notify_error(format!("Couldn't write trace, stopping it: {err}"));
+                self.trace = None;
+            }
+        }
+
+        let pre_step = self.events.as_ref().map(|events| events.before_step(emu));
+        let before = (emu.cpu.regs.pc, emu.cpu.regs.sp);
+        let access = access::next(emu);
+
+        let (cpu_status, draw_ready) = emu.step();
+        let access = access::interrupt(emu, before).unwrap_or(access);
+        let cycles = pacing::step_cycles(emu, &cpu_status);
+
+        self.apu.tick(cycles);
+        self.mapper.tick(cycles);
+        self.memory_hit = self.memory_breakpoints.iter().copied().find(|breakpoint| breakpoint.hit_by(access));
+
+        self.track_writes(access);
+
+        if let (Some(events), Some(pre_step)) = (self.events.as_mut(), pre_step) {
+            events.after_step(emu, pre_step, self.frames, self.cycles);
+        }
+
+        self.cycles += cycles;
+        self.pacer.advance(cycles);
+
+        self.audio_cycles += cycles;
+        if self.audio_cycles >= AUDIO_PULL_CYCLES {
+            self.audio_cycles = 0;
+            self.pull_audio();
+        }
+
+        if draw_ready {
+            self.apply_freezes(emu);
+            emu.set_drawn();
+            self.present(emu);
+            self.dump_state(emu);
+            self.flush_events();
+
+            self.frames += 1;
+            if self.frames.is_multiple_of(rewind::FRAMES_PER_SNAPSHOT) {
+                self.rewind.push(&Snapshot::for_rewind(emu, &self.mapper, self.cart_ram.clone()).to_bytes(), self.cycles);
+            }
+
+            if self.frames.is_multiple_of(battery::FLUSH_INTERVAL) {
+                self.flush_battery(emu, false);
+            }
+        }
+
+        // if let Some(serial) = emu.read_serial() {
+        //     print!("{}", serial as char);
+        // }
+        
+        match self.status {
+            EmuStatus::Break
+            | EmuStatus::Stepping
+            | EmuStatus::Stopped => {
+                self.dump_state(emu);
+                self.flush_events();
+            },
+            _ => {}
+        }
+        
+        (cpu_status, draw_ready)
+    }
+
+    /// Runs to the end of the frame to draw it, keeping up with the mapper, APU and cartridge RAM but
+    /// nothing else, so rewinding isn't traced, logged, heard or recorded into the rewind buffer
+    fn redraw(&mut self, emu: &mut Gbc<Mmu>) {
+        let mut cycles = 0;
+
+        while cycles < pacing::CYCLES_PER_FRAME {
+            self.apply_movie_input(emu);
+
+            let before = (emu.cpu.regs.pc, emu.cpu.regs.sp);
+            let access = access::next(emu);
+            let (cpu_status, draw_ready) = emu.step();
+            let access = access::interrupt(emu, before).unwrap_or(access);
+            let step_cycles = pacing::step_cycles(emu, &cpu_status);
+
+            self.apu.tick(step_cycles);
+            self.mapper.tick(step_cycles);
+            self.track_writes(access);
+            self.cycles += step_cycles;
+            cycles += step_cycles;
+
+            if draw_ready || !matches!(cpu_status, Ok(CpuStatus::Run(_))) {
+                break;
+            }
+        }
+
+        self.apply_freezes(emu);
+        emu.set_drawn();
+        self.apu.take_samples();
+    }
+
+    /// Presses and releases whatever the movie has due by now
+    fn apply_movie_input(&mut self, emu: &mut Gbc<Mmu>) {
+        while let Some(event) = self.movie.as_mut().and_then(|movie| movie.due(self.cycles)) {
+            if event.pressed {
+                emu.press_button(event.button);
+            } else {
+                emu.release_button(event.button);
+            }
+        }
+    }
+
+    /// Keeps the mapper, APU and copy of cartridge RAM up with the writes a step made
+    fn track_writes(&mut self, access: Access) {
+        for access::Write { addr, value } in access.writes.into_iter().flatten() {
+            self.mapper.write(addr, value);
+            self.apu.write(addr, value);
+            self.track_cart_ram(addr, value);
+        }
+    }
+
+    fn track_cart_ram(&mut self, addr: u16, value: u8) {
+        if let Some(offset) = self.mapper.ram_offset(addr) {
+            if let Some(byte) = self.cart_ram.get_mut(offset) {
+                *byte = value;
+            }
+        }
+    }
+
+    /// Reads cartridge RAM in again after something other than a step changed it
+    fn sync_cart_ram(&mut self, emu: &mut Gbc<Mmu>) {
+        self.cart_ram = self.mapper.read_ram(&mut emu.cpu.memory);
+    }
+
+    fn set_run_target(&mut self, emu: &mut Gbc<Mmu>, target: RunTarget) {
+        self.clear_run_target(emu);
+
+        if let RunTarget::Address { addr, .. } = target {
+            emu.cpu.breakpoint_controls.set(CpuEvent::Pc(addr));
+        }
+
+        self.run_target = Some(target);
+    }
+
+    fn clear_run_target(&mut self, emu: &mut Gbc<Mmu>) {
+        if let Some(RunTarget::Address { addr, .. }) = self.run_target.take() {
+            if !self.user_pc_breakpoints.contains(&addr) {
+                emu.cpu.breakpoint_controls.unset(CpuEvent::Pc(addr));
+            }
+        }
+    }
+
+    fn apply_latched_presses(&mut self, emu: &mut Gbc<Mmu>) {
+        for button in std::mem::take(&mut self.latched_presses) {
+            if self.user_input(button, true) {
+                emu.press_button(button);
+            }
+        }
+    }
+
+    fn apply_latched_releases(&mut self, emu: &mut Gbc<Mmu>) {
+        for button in std::mem::take(&mut self.latched_releases) {
+            if self.user_input(button, false) {
+                emu.release_button(button);
+            }
+        }
+    }
+
+    fn pull_audio(&mut self) {
+        let samples = self.apu.take_samples();
+        let volume = if self.muted { 0.0 } else { self.volume };
+
+        // turbo runs as fast as it can, so there's no rate it could be played back at
+        if self.pacer.turbo() {
+            return;
+        }
+
+        // sound is played back as fast or slow as the emu is running, so it keeps up with the sink
+        self.resampler.set_speed(self.pacer.speed());
+        self.audio_buf.clear();
+        self.resampler.process(&samples, &mut self.audio_buf);
+
+        for sample in self.audio_buf.iter_mut() {
+            sample[0] *= volume;
+            sample[1] *= volume;
+        }
+
+        self.audio.push(&self.audio_buf);
+    }
+
+    fn present(&mut self, emu: &Gbc<Mmu>) {
+        self.sink.present(emu);
+    }
+
+    fn set_status(&mut self, status: EmuStatus) {
+        self.status = status;
+        self.sink.set_status(status);
+    }
+
+    fn load_battery(&mut self, emu: &mut Gbc<Mmu>) {
+        let Some(ref rom_path) = self.rom_path else {
+            return;
+        };
+
+        if !self.mapper.battery {
+            return;
+        }
+
+        let path = battery::path(rom_path);
+        match std::fs::read(&path) {
+            Ok(data) => {
+                battery::decode(emu, &mut self.mapper, &data);
+                self.notify(format!("Loaded {}", path.display()));
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {},
+            Err(err) => self.notify_error(format!("Couldn't read {}: {err}", path.display())),
+        }
+
+        self.battery_ram = self.mapper.read_ram(&mut emu.cpu.memory);
+    }
+
+    /// Writes cartridge RAM to the .sav if it changed since the last flush, or always if `force` is set
+    fn flush_battery(&mut self, emu: &mut Gbc<Mmu>, force: bool) {
+        let Some(ref rom_path) = self.rom_path else {
+            return;
+        };
+
+        if !self.mapper.battery || self.battery_tainted {
+            return;
+        }
+
+        let ram = self.mapper.read_ram(&mut emu.cpu.memory);
+        if !force && ram == self.battery_ram {
+            return;
+        }
+
+        let path = battery::path(rom_path);
+        match std::fs::write(&path, battery::encode(emu, &self.mapper)) {
+            Ok(()) => self.battery_ram = ram,
+            Err(err) => self.notify_error(format!("Couldn't write {}: {err}", path.display())),
+        }
+    }
+
+    /// Writes out anything that shouldn't be lost when the runner goes away
+    fn shutdown(&mut self, emu: &mut Gbc<Mmu>) {
+        self.flush_battery(emu, true);
+        self.stop_movie();
+        self.stop_trace();
+    }
+
+    /// Rewrites frozen memory and GameShark codes
+    fn apply_freezes(&mut self, emu: &mut Gbc<Mmu>) {
+        let writes = self.freezes.iter().flat_map(|(addr, bytes)| bytes.iter().enumerate().map(|(i, &byte)| (addr.wrapping_add(i as u16), byte))).collect::<Vec<_>>();
+
+        for (addr, value) in writes {
+            emu.cpu.memory.set(addr, value);
+            self.track_cart_ram(addr, value);
+        }
+
+        for code in self.ram_cheats.clone() {
+            let Code::GameShark { bank, addr, value } = code else {
+                continue;
+            };
+
+            match addr {
+                0xA000..=0xBFFF if self.mapper.ram_len > 0 => {
+                    self.mapper.write_bank(&mut emu.cpu.memory, bank, addr, value);
+
+                    if let Some(byte) = self.mapper.bank_offset(bank, addr).and_then(|offset| self.cart_ram.get_mut(offset)) {
+                        *byte = value;
+                    }
+                },
+                // banked WRAM, with SVBK put back for the game afterwards
+                0xD000..=0xDFFF if bank != 0 && vram::is_cgb(&self.rom) => {
+                    let svbk = emu.cpu.memory.load(SVBK).unwrap_or(0);
+                    emu.cpu.memory.set(SVBK, bank);
+                    emu.cpu.memory.set(addr, value);
+                    emu.cpu.memory.set(SVBK, svbk);
+                },
+                _ => emu.cpu.memory.set(addr, value),
+            }
+        }
+    }
+
+    fn set_cheats(&mut self, emu: &mut Gbc<Mmu>, codes: Vec<Code>) {
+        let mut rom_patches = Vec::new();
+        self.ram_cheats.clear();
+
+        for code in codes {
+            match code {
+                Code::GameGenie { .. } => rom_patches.push(code),
+                Code::GameShark { .. } => self.ram_cheats.push(code),
+            }
+        }
+
+        // swapping the ROM out is only worth it when the Game Genie codes actually changed
+        if rom_patches != self.rom_patches {
+            self.rom_patches = rom_patches;
+
+            // the cartridge's RAM and bank registers are put back in case loading a ROM resets them
+            let ram = self.mapper.read_ram(&mut emu.cpu.memory);
+            emu.load_rom(&self.patched_rom());
+            self.mapper.write_ram(&mut emu.cpu.memory, &ram);
+        }
+
+        self.apply_freezes(emu);
+    }
+
+    /// The ROM with the Game Genie codes written into it, which is what the emu actually runs
+    fn patched_rom(&self) -> Vec<u8> {
+        cheats::patch_rom(&self.rom, &self.rom_patches)
+    }
+
+    /// Sends whatever the event log picked up since it was last sent
+    fn flush_events(&mut self) {
+        let Some(events) = self.events.as_mut().map(EventLogger::take) else {
+            return;
+        };
+
+        if !events.is_empty() {
+            let _ = self.sender.send(EmuMsgOut::Events(events));
+        }
+    }
+
+    fn start_trace(&mut self, path: &Path, filter: TraceFilter, pin_ly: bool) {
+        self.stop_trace();
+
+        match Tracer::create(path, filter) {
+            Ok(mut tracer) => {
+                tracer.pin_ly = pin_ly;
+                self.trace = Some(tracer);
+            },
+            Err(err) => self.notify_error(format!("Couldn't start trace at {}: {err}", path.display())),
+        }
+    }
+
+    fn stop_trace(&mut self) {
+        if let Some(mut tracer) = self.trace.take() {
+            if let Err(err) = tracer.flush() {
+                self.notify_error(format!("Couldn't finish writing trace: {err}"));
+            }
+
+            self.notify(format!("Trace finished with {} lines", tracer.lines));
+        }
+    }
+
+    /// Returns whether input from the user should reach the emu
+    fn user_input(&mut self, button: gbc::Button, pressed: bool) -> bool {
+        let Some(ref mut movie) = self.movie else {
+            return true;
+        };
+
+        let allowed = movie.user_input(self.cycles, button, pressed);
+        self.send_movie_status();
+        allowed
+    }
+
+    /// Lets the movie know the emu jumped to a different point in time
+    fn seek_movie(&mut self) {
+        if let Some(ref mut movie) = self.movie {
+            movie.seek(self.cycles);
+            self.send_movie_status();
+        }
+    }
+
+    fn start_recording(&mut self, emu: &mut Gbc<Mmu>, start: MovieStart) {
+        self.stop_movie();
+
+        let start_state = match start {
+            MovieStart::PowerOn => {
+                // the real save is about to be swapped out for blank RAM, so get it written first
+                self.flush_battery(emu, false);
+                self.battery_tainted = true;
+
+                *emu = Self::power_on(&self.patched_rom());
+                self.mapper = Mapper::new(&self.rom);
+                self.apu.sync(&emu.cpu.memory);
+                self.cycles = 0;
+                self.sync_cart_ram(emu);
+                None
+            },
+            MovieStart::Now => {
+                let state = savestate::encode(emu, &self.mapper, self.rom_checksum, self.cycles);
+
+                // playback starts by loading the state, which can't put back everything the emu had going,
+                // so recording starts from the same load to keep the two in step. It was only just made, so it loads
+                let _ = savestate::decode(emu, &mut self.mapper, self.rom_checksum, &state);
+                self.apu.sync(&emu.cpu.memory);
+                self.sync_cart_ram(emu);
+                Some(state)
+            },
+        };
+
+        // history from before the movie isn't part of it
+        self.rewind.clear();
+        self.movie = Some(MovieSession::recording(Movie {
+            rom_checksum: self.rom_checksum,
+            rerecords: 0,
+            start_state,
+            events: Vec::new(),
+        }));
+
+        self.notify("Recording movie".to_string());
+        self.send_movie_status();
+    }
+
+    fn start_playback(&mut self, emu: &mut Gbc<Mmu>) {
+        let Some(ref rom_path) = self.rom_path else {
+            return;
+        };
+
+        let path = movie::path(rom_path);
+        let movie = match std::fs::read(&path).map(|data| movie::decode(self.rom_checksum, &data)) {
+            Ok(Ok(movie)) => movie,
+            Ok(Err(err)) => {
+                self.notify_error(format!("Couldn't load {}: {err}", path.display()));
+                return;
+            },
+            Err(err) => {
+                self.notify_error(format!("Couldn't read {}: {err}", path.display()));
+                return;
+            }
+        };
+
+        self.stop_movie();
+
+        // either way the movie brings its own cartridge RAM
+        self.flush_battery(emu, false);
+        self.battery_tainted = true;
+
+        match movie.start_state {
+            None => {
+                *emu = Self::power_on(&self.patched_rom());
+                self.mapper = Mapper::new(&self.rom);
+                self.apu.sync(&emu.cpu.memory);
+                self.cycles = 0;
+                self.sync_cart_ram(emu);
+            },
+            Some(ref state) => match savestate::decode(emu, &mut self.mapper, self.rom_checksum, state) {
+                Ok(cycles) => {
+                    self.cycles = cycles;
+                    self.apu.sync(&emu.cpu.memory);
+                    self.sync_cart_ram(emu);
+                },
+                Err(err) => {
+                    self.notify_error(format!("Couldn't load the movie's starting state: {err}"));
+                    return;
+                }
+            },
+        }
+
+        self.rewind.clear();
+        self.movie = Some(MovieSession::playing(movie));
+
+        self.notify("Playing movie".to_string());
+        self.send_movie_status();
+    }
+
+    /// Ends the active movie, writing it out if anything was recorded
+    fn stop_movie(&mut self) {
+        let Some(session) = self.movie.take() else {
+            return;
+        };
+
+        self.send_movie_status();
+
+        let Some(ref rom_path) = self.rom_path else {
+            return;
+        };
+
+        if !session.dirty {
+            return;
+        }
+
+        let path = movie::path(rom_path);
+        match std::fs::write(&path, movie::encode(&session.movie)) {
+            Ok(()) => self.notify(format!("Saved movie to {}", path.display())),
+            Err(err) => self.notify_error(format!("Couldn't write {}: {err}", path.display())),
+        }
+    }
+
+    /// Shows `text` to the user
+    fn notify(&self, text: String) {
+        let _ = self.sender.send(EmuMsgOut::Status(text));
+    }
+
+    fn notify_error(&self, text: String) {
+        let _ = self.sender.send(EmuMsgOut::Error(text));
+    }
+
+    fn send_movie_status(&self) {
+        let _ = self.sender.send(EmuMsgOut::Movie(self.movie.as_ref().map(MovieSession::status)));
+    }
+
+    fn save_state(&self, emu: &mut Gbc<Mmu>, slot: usize) {
+        let Some(ref rom_path) = self.rom_path else {
+            return;
+        };
+
+        let path = savestate::path(rom_path, slot);
+        let data = savestate::encode(emu, &self.mapper, self.rom_checksum, self.cycles);
+
+        match std::fs::write(&path, data) {
+            Ok(()) => self.notify(format!("Saved state to slot {slot}")),
+            Err(err) => self.notify_error(format!("Couldn't write {}: {err}", path.display())),
+        }
+    }
+
+    /// Returns whether the state was loaded
+    fn load_state(&mut self, emu: &mut Gbc<Mmu>, slot: usize) -> bool {
+        let Some(ref rom_path) = self.rom_path else {
+            return false;
+        };
+
+        let path = savestate::path(rom_path, slot);
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                self.notify_error(format!("Couldn't read {}: {err}", path.display()));
+                return false;
+            }
+        };
+
+        match savestate::decode(emu, &mut self.mapper, self.rom_checksum, &data) {
+            Ok(cycles) => {
+                self.notify(format!("Loaded state from slot {slot}"));
+                self.cycles = cycles;
+                self.apu.sync(&emu.cpu.memory);
+                self.sync_cart_ram(emu);
+                self.seek_movie();
+                true
+            },
+            Err(err) => {
+                self.notify_error(format!("Couldn't load {}: {err}", path.display()));
+                false
+            }
+        }
+    }
+
+    /// Sends the UI a copy of the emu's state. A runner that's being replaced can outlive the UI's end of
+    /// the channel, so a closed channel is ignored rather than stopping it before it gets to write the .sav
+    fn dump_state(&self, emu: &mut Gbc<Mmu>) {
+        let regs = emu.cpu.regs;
+        let io_regs = emu.cpu.dump_io_regs();
+        let memory = emu.cpu.memory.load_block(0, u16::MAX);
+        let instruction_byte = emu.cpu.memory.load(emu.cpu.regs.pc).unwrap_or(0);
+        let (instruction_byte, prefixed) = if instruction_byte == 0xCB {
+            (emu.cpu.memory.load(emu.cpu.regs.pc + 1).unwrap_or(0), true)
+        } else {
+            (instruction_byte, false)
+        };
+
+        let next_instruction = gbc::Instruction::from_byte(prefixed, instruction_byte).unwrap_or(gbc::Instruction::NOP);
+        let cgb = vram::is_cgb(&self.rom);
+
+        let state = StateDump {
+            next_instruction,
+            regs,
+            io_regs,
+            memory,
+            rom_bank: self.mapper.rom_bank(),
+            vram_banks: vram::read_banks(&mut emu.cpu.memory, cgb),
+            palette_ram: cgb.then(|| vram::read_palette_ram(&mut emu.cpu.memory)),
+        };
+        
+        let _ = self.sender.send(EmuMsgOut::State(state));
+    }
+}
+
+/// Length of the instruction if `opcode` is a CALL or RST, which step over runs through
+fn call_len(opcode: u8) -> Option<u16> {
+    match opcode {
+        // CALL nn, CALL cc,nn
+        0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC => Some(3),
+        // RST
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => Some(1),
+        _ => None,
+    }
+}
+
+/// RET, RETI and RET cc
+fn is_return(opcode: u8) -> bool {
+    matches!(opcode, 0xC9 | 0xD9 | 0xC0 | 0xC8 | 0xD0 | 0xD8)
+}
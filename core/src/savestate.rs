@@ -0,0 +1,387 @@
+//! Save states, built from the parts of the emu the core makes public: CPU registers, DIV, HALT and
+//! STOP, the framebuffer, the address space and every bank of cartridge RAM, plus the mapper
+//! registers the runner tracks in [`Mapper`].
+//!
+//! These are quick saves for picking a game back up, not exact snapshots of the hardware. What the
+//! core keeps to itself can't be saved or loaded:
+//!
+//! - the PPU's position in the frame. Loading turns the LCD off and back on to get at VRAM and OAM,
+//!   so the frame after a load always starts again from line 0
+//! - the APU's channel timers, lengths and envelopes, and whether each channel is playing. These
+//!   carry on from before the load until the game next starts the channel
+//! - the timer's prescaler, and a serial transfer that was under way
+
+use std::{fmt::Display, path::{Path, PathBuf}};
+
+use gbc::{memory::Memory, Gbc, Mmu};
+
+use crate::mbc::Mapper;
+
+/// Number of save state slots, bound to F1 through F10
+pub const SLOTS: usize = 10;
+
+const MAGIC: &[u8; 4] = b"BWSS";
+/// Bump this whenever the layout changes
+const VERSION: u16 = 3;
+const HEADER_LEN: usize = 20;
+
+/// What gets copied out of the address space, inclusive. ROM comes from the rom, cartridge RAM is
+/// saved a bank at a time, and echo RAM and the unusable area after OAM are left out
+const REGIONS: [(u16, u16); 4] = [(0x8000, 0x9FFF), (0xC000, 0xDFFF), (0xFE00, 0xFE9F), (0xFF00, 0xFFFF)];
+const MEMORY_LEN: usize = 0x2000 + 0x2000 + 0xA0 + 0x100;
+
+const LCDC: u16 = 0xFF40;
+const NR52: u16 = 0xFF26;
+/// Registers that aren't written back as part of the address space. DIV is restored from the
+/// counter instead, NR52 goes first since turning sound off clears the other sound registers, LCDC
+/// goes last, and writes to the rest would start a serial transfer, trigger a sound channel, start
+/// a DMA, a speed switch or reset LY
+const SKIPPED: [u16; 12] = [0xFF02, 0xFF04, 0xFF14, 0xFF19, 0xFF1E, 0xFF23, NR52, LCDC, 0xFF44, 0xFF46, 0xFF4D, 0xFF55];
+
+#[derive(Clone, Copy, Debug)]
+pub enum SaveStateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    WrongRom { expected: u16, found: u16 },
+    Truncated,
+}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a save state"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported save state version {version}"),
+            Self::WrongRom { expected, found } => write!(f, "save state is for a different rom (checksum {found:#06X}, expected {expected:#06X})"),
+            Self::Truncated => write!(f, "save state is truncated"),
+        }
+    }
+}
+
+/// Everything a save state holds, taken out of the emu
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// A, F, B, C, D, E, H and L
+    pub regs: [u8; 8],
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    pub halted: bool,
+    pub stopped: bool,
+    pub div: u16,
+    pub mapper: [u8; 5],
+    pub cart_ram: Vec<u8>,
+    /// Each of [`REGIONS`], one after another
+    pub memory: Vec<u8>,
+    pub fb: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Needs `emu` mutably to switch through the cartridge RAM banks, which get put back afterwards
+    pub fn take(emu: &mut Gbc<Mmu>, mapper: &Mapper) -> Self {
+        let cart_ram = mapper.read_ram(&mut emu.cpu.memory);
+
+        Self {
+            fb: emu.cpu.ppu.fb.clone(),
+            ..Self::for_rewind(emu, mapper, cart_ram)
+        }
+    }
+
+    /// A snapshot for the rewind buffer, which takes one every frame. Cartridge RAM comes from the
+    /// runner's copy of it instead of switching banks, and the framebuffer is left out, since the
+    /// runner draws it again by running a frame on from the snapshot
+    pub fn for_rewind(emu: &Gbc<Mmu>, mapper: &Mapper, cart_ram: Vec<u8>) -> Self {
+        let cpu = &emu.cpu;
+        let regs = &cpu.regs;
+
+        Self {
+            regs: [regs.a, regs.f.as_byte(), regs.b, regs.c, regs.d, regs.e, regs.h, regs.l],
+            sp: regs.sp,
+            pc: regs.pc,
+            ime: regs.ime,
+            halted: cpu.halted,
+            stopped: cpu.stop,
+            div: cpu.div,
+            mapper: mapper.registers(),
+            cart_ram,
+            memory: REGIONS.into_iter()
+                .flat_map(|(start, end)| start..=end)
+                .map(|addr| cpu.memory.load(addr).unwrap_or(0xFF))
+                .collect(),
+            fb: Vec::new(),
+        }
+    }
+
+    /// Puts the emu back how it was, and `mapper` along with it
+    pub fn restore(&self, emu: &mut Gbc<Mmu>, mapper: &mut Mapper) {
+        let memory = &mut emu.cpu.memory;
+        let saved = |addr: u16| self.memory.get(offset(addr)).copied().unwrap_or(0);
+
+        mapper.set_registers(self.mapper);
+        if self.cart_ram.len() == mapper.ram_len {
+            mapper.write_ram(memory, &self.cart_ram);
+        } else {
+            mapper.restore(memory);
+        }
+
+        memory.set(NR52, saved(NR52));
+        // VRAM and OAM can't be written while the PPU is using them
+        memory.set(LCDC, 0);
+
+        let addrs = REGIONS.into_iter().flat_map(|(start, end)| start..=end);
+        for (addr, &byte) in addrs.zip(&self.memory) {
+            if !SKIPPED.contains(&addr) {
+                memory.set(addr, byte);
+            }
+        }
+
+        memory.set(LCDC, saved(LCDC));
+
+        let [a, f, b, c, d, e, h, l] = self.regs;
+        let regs = &mut emu.cpu.regs;
+        (regs.a, regs.b, regs.c, regs.d, regs.e, regs.h, regs.l) = (a, b, c, d, e, h, l);
+        regs.f = gbc::Flags::new();
+        regs.f.set_bits(f);
+        regs.sp = self.sp;
+        regs.pc = self.pc;
+        regs.ime = self.ime;
+
+        emu.cpu.div = self.div;
+        emu.cpu.halted = self.halted;
+        emu.cpu.stop = self.stopped;
+
+        // rewind snapshots leave it out
+        if emu.cpu.ppu.fb.len() == self.fb.len() {
+            emu.cpu.ppu.fb.copy_from_slice(&self.fb);
+        }
+    }
+
+    /// Layout, all LE:
+    /// ```text
+    /// 0..8    A F B C D E H L
+    /// 8..10   SP
+    /// 10..12  PC
+    /// 12      IME, HALT and STOP in bits 0, 1 and 2
+    /// 13..15  DIV
+    /// 15..20  mapper registers
+    /// ..      cartridge RAM length (u32), cartridge RAM
+    /// ..      the address space regions
+    /// ..      framebuffer length (u32), framebuffer
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(28 + self.cart_ram.len() + self.memory.len() + self.fb.len());
+
+        out.extend_from_slice(&self.regs);
+        out.extend_from_slice(&self.sp.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.push(self.ime as u8 | (self.halted as u8) << 1 | (self.stopped as u8) << 2);
+        out.extend_from_slice(&self.div.to_le_bytes());
+        out.extend_from_slice(&self.mapper);
+        out.extend_from_slice(&(self.cart_ram.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.cart_ram);
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&(self.fb.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.fb);
+
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SaveStateError> {
+        let mut reader = Reader { data, pos: 0 };
+
+        let regs = reader.take(8)?.try_into().unwrap();
+        let sp = reader.u16()?;
+        let pc = reader.u16()?;
+        let flags = reader.take(1)?[0];
+        let div = reader.u16()?;
+        let mapper = reader.take(5)?.try_into().unwrap();
+        let cart_ram_len = reader.u32()? as usize;
+        let cart_ram = reader.take(cart_ram_len)?.to_vec();
+        let memory = reader.take(MEMORY_LEN)?.to_vec();
+        let fb_len = reader.u32()? as usize;
+        let fb = reader.take(fb_len)?.to_vec();
+
+        Ok(Self {
+            regs,
+            sp,
+            pc,
+            ime: flags & 0b001 != 0,
+            halted: flags & 0b010 != 0,
+            stopped: flags & 0b100 != 0,
+            div,
+            mapper,
+            cart_ram,
+            memory,
+            fb,
+        })
+    }
+}
+
+/// Where `addr` is in [`Snapshot::memory`]
+fn offset(addr: u16) -> usize {
+    let mut offset = 0;
+
+    for (start, end) in REGIONS {
+        if (start..=end).contains(&addr) {
+            return offset + (addr - start) as usize;
+        }
+        offset += (end - start) as usize + 1;
+    }
+
+    usize::MAX
+}
+
+/// Save states live next to the rom, as `<rom>.ss<slot>`
+pub fn path(rom_path: &Path, slot: usize) -> PathBuf {
+    rom_path.with_extension(format!("ss{slot}"))
+}
+
+/// The global checksum from the cartridge header, used to make sure states are loaded into the right game
+pub fn rom_checksum(rom: &[u8]) -> u16 {
+    rom.get(0x14E..0x150).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])).unwrap_or(0)
+}
+
+pub fn encode(emu: &mut Gbc<Mmu>, mapper: &Mapper, rom_checksum: u16, cycles: u64) -> Vec<u8> {
+    with_header(rom_checksum, cycles, &Snapshot::take(emu, mapper).to_bytes())
+}
+
+/// Returns the emulated cycle count the state was saved at. Nothing is touched if the state is bad
+pub fn decode(emu: &mut Gbc<Mmu>, mapper: &mut Mapper, rom_checksum: u16, data: &[u8]) -> Result<u64, SaveStateError> {
+    let (cycles, payload) = split_header(rom_checksum, data)?;
+    Snapshot::from_bytes(payload)?.restore(emu, mapper);
+    Ok(cycles)
+}
+
+/// Layout:
+/// ```text
+/// 0..4    magic "BWSS"
+/// 4..6    format version (LE)
+/// 6..8    rom global checksum (LE)
+/// 8..16   emulated T-cycles since power-on (LE)
+/// 16..20  payload length (LE)
+/// 20..    payload, as written by Snapshot::to_bytes
+/// ```
+fn with_header(rom_checksum: u16, cycles: u64, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&rom_checksum.to_le_bytes());
+    out.extend_from_slice(&cycles.to_le_bytes());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+
+    out
+}
+
+/// Checks the header against `rom_checksum`, returning the cycle count and the payload
+fn split_header(rom_checksum: u16, data: &[u8]) -> Result<(u64, &[u8]), SaveStateError> {
+    let mut reader = Reader { data, pos: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err(SaveStateError::BadMagic);
+    }
+
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+
+    let found = reader.u16()?;
+    if found != rom_checksum {
+        return Err(SaveStateError::WrongRom { expected: rom_checksum, found });
+    }
+
+    let cycles = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+    let len = reader.u32()? as usize;
+
+    Ok((cycles, reader.take(len)?))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        let out = self.data.get(self.pos..self.pos + len).ok_or(SaveStateError::Truncated)?;
+        self.pos += len;
+        Ok(out)
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            regs: [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            sp: 0xFFFE,
+            pc: 0x0150,
+            ime: true,
+            halted: false,
+            stopped: true,
+            div: 0xABCD,
+            mapper: [0x0A, 0x05, 0x00, 0x02, 0x01],
+            cart_ram: (0..0x2000).map(|i| i as u8).collect(),
+            memory: (0..MEMORY_LEN).map(|i| (i * 7) as u8).collect(),
+            fb: vec![0x55; 160 * 144 * 3],
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = snapshot();
+        assert_eq!(Snapshot::from_bytes(&snapshot.to_bytes()).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn snapshot_truncated() {
+        let bytes = snapshot().to_bytes();
+        assert!(matches!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]), Err(SaveStateError::Truncated)));
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let data = with_header(0x1234, 99_999, b"payload");
+        let (cycles, payload) = split_header(0x1234, &data).unwrap();
+
+        assert_eq!(cycles, 99_999);
+        assert_eq!(payload, b"payload");
+    }
+
+    #[test]
+    fn header_errors() {
+        let data = with_header(0x1234, 0, b"payload");
+
+        assert!(matches!(split_header(0x4321, &data), Err(SaveStateError::WrongRom { expected: 0x4321, found: 0x1234 })));
+        assert!(matches!(split_header(0x1234, &data[..HEADER_LEN + 3]), Err(SaveStateError::Truncated)));
+
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        assert!(matches!(split_header(0x1234, &bad), Err(SaveStateError::BadMagic)));
+
+        let mut old = data;
+        old[4] = 2;
+        assert!(matches!(split_header(0x1234, &old), Err(SaveStateError::UnsupportedVersion(2))));
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(offset(0x8000), 0);
+        assert_eq!(offset(0xC000), 0x2000);
+        assert_eq!(offset(0xFE00), 0x4000);
+        assert_eq!(offset(0xFF40), 0x40A0 + 0x40);
+        assert_eq!(offset(0xFFFF), MEMORY_LEN - 1);
+        assert_eq!(offset(0xA000), usize::MAX);
+    }
+}
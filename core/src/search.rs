@@ -0,0 +1,185 @@
+//! Iterative RAM search for finding game variables, working on `StateDump::memory` snapshots.
+//!
+//! A search starts with every RAM address as a candidate, and each filter
+//! narrows them down by comparing the latest snapshot against the one before it.
+
+/// Where game variables can live: cartridge RAM, WRAM and HRAM
+pub const REGIONS: [(u16, u16); 3] = [(0xA000, 0xBFFF), (0xC000, 0xDFFF), (0xFF80, 0xFFFE)];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Width {
+    #[default]
+    Byte,
+    /// Little endian, like the CPU reads them
+    Word,
+}
+
+impl Width {
+    pub fn max(self) -> u16 {
+        match self {
+            Self::Byte => u8::MAX as u16,
+            Self::Word => u16::MAX,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Same as when the search started
+    Equal,
+    /// Different from the last snapshot
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Value(u16),
+}
+
+pub fn read(memory: &[u8], addr: u16, width: Width) -> u16 {
+    let byte = |addr: u16| memory.get(addr as usize).copied().unwrap_or(0) as u16;
+
+    match width {
+        Width::Byte => byte(addr),
+        Width::Word => byte(addr) | byte(addr.wrapping_add(1)) << 8,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Search {
+    pub width: Width,
+    first: Vec<u8>,
+    prev: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl Search {
+    pub fn new(memory: &[u8], width: Width) -> Self {
+        // words need their high byte in the same region
+        let last_offset = match width {
+            Width::Byte => 0,
+            Width::Word => 1,
+        };
+
+        let candidates = REGIONS.iter()
+            .flat_map(|&(start, end)| start..=end - last_offset)
+            .collect();
+
+        Self {
+            width,
+            first: memory.to_vec(),
+            prev: memory.to_vec(),
+            candidates,
+        }
+    }
+
+    /// Keeps the candidates that pass `filter` in `memory`, which becomes the new last snapshot
+    pub fn filter(&mut self, memory: &[u8], filter: Filter) {
+        let width = self.width;
+
+        self.candidates.retain(|&addr| {
+            let now = read(memory, addr, width);
+            let prev = read(&self.prev, addr, width);
+
+            match filter {
+                Filter::Equal => now == read(&self.first, addr, width),
+                Filter::Changed => now != prev,
+                Filter::Unchanged => now == prev,
+                Filter::Increased => now > prev,
+                Filter::Decreased => now < prev,
+                Filter::Value(value) => now == value,
+            }
+        });
+
+        self.prev = memory.to_vec();
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    /// Value at `addr` as of the last snapshot
+    pub fn previous(&self, addr: u16) -> u16 {
+        read(&self.prev, addr, self.width)
+    }
+
+    pub fn remove(&mut self, addr: u16) {
+        self.candidates.retain(|&candidate| candidate != addr);
+    }
+}
+
+/// An address being kept an eye on, and optionally held at a value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watch {
+    pub addr: u16,
+    pub width: Width,
+    pub label: String,
+    /// Value written back every frame, if frozen
+    pub freeze: Option<u16>,
+}
+
+impl Watch {
+    /// Bytes to write for a freeze at `value`, in memory order
+    pub fn bytes(&self, value: u16) -> Vec<u8> {
+        match self.width {
+            Width::Byte => vec![value as u8],
+            Width::Word => value.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(values: &[(u16, u8)]) -> Vec<u8> {
+        let mut memory = vec![0; 0x10000];
+
+        for &(addr, value) in values {
+            memory[addr as usize] = value;
+        }
+
+        memory
+    }
+
+    #[test]
+    fn candidates_stay_in_regions() {
+        let search = Search::new(&memory(&[]), Width::Word);
+
+        assert_eq!(search.candidates().first(), Some(&0xA000));
+        assert_eq!(search.candidates().last(), Some(&0xFFFD));
+        assert!(!search.candidates().contains(&0xBFFF));
+        assert!(!search.candidates().contains(&0xE000));
+    }
+
+    #[test]
+    fn filters_narrow_down() {
+        let mut search = Search::new(&memory(&[(0xC000, 3), (0xC001, 3)]), Width::Byte);
+
+        search.filter(&memory(&[(0xC000, 2), (0xC001, 4), (0xC002, 1)]), Filter::Changed);
+        assert_eq!(search.candidates(), &[0xC000, 0xC001, 0xC002]);
+
+        search.filter(&memory(&[(0xC000, 1), (0xC001, 5), (0xC002, 1)]), Filter::Decreased);
+        assert_eq!(search.candidates(), &[0xC000]);
+        assert_eq!(search.previous(0xC000), 1);
+
+        search.filter(&memory(&[(0xC000, 1)]), Filter::Unchanged);
+        search.filter(&memory(&[(0xC000, 3)]), Filter::Equal);
+        assert_eq!(search.candidates(), &[0xC000]);
+
+        search.filter(&memory(&[(0xC000, 9)]), Filter::Value(8));
+        assert!(search.candidates().is_empty());
+    }
+
+    #[test]
+    fn words_are_little_endian() {
+        let mut search = Search::new(&memory(&[]), Width::Word);
+        let memory = memory(&[(0xFF80, 0x34), (0xFF81, 0x12)]);
+
+        search.filter(&memory, Filter::Value(0x1234));
+        assert_eq!(search.candidates(), &[0xFF80]);
+        assert_eq!(search.previous(0xFF80), 0x1234);
+
+        let watch = Watch { addr: 0xFF80, width: Width::Word, label: String::new(), freeze: None };
+        assert_eq!(watch.bytes(0x1234), vec![0x34, 0x12]);
+    }
+}
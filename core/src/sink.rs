@@ -0,0 +1,47 @@
+use std::sync::{Arc, Mutex};
+
+use gbc::{Gbc, Mmu};
+
+use crate::runner::EmuStatus;
+
+/// Where the runner sends its output
+pub trait FrameSink: Send + 'static {
+    /// Called whenever the emu finishes a frame, or its state changes outside of normal execution
+    fn present(&mut self, emu: &Gbc<Mmu>);
+
+    /// Called whenever the runner's status changes
+    fn set_status(&mut self, status: EmuStatus);
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessFrame {
+    /// RGB, `WIDTH * HEIGHT * 3` bytes
+    pub fb: Vec<u8>,
+    /// Number of frames presented so far
+    pub frames: usize,
+    pub status: EmuStatus,
+}
+
+/// Keeps the latest frame around for whoever's holding a clone of it, for tests, CI and servers
+#[derive(Clone, Debug, Default)]
+pub struct HeadlessSink {
+    inner: Arc<Mutex<HeadlessFrame>>,
+}
+
+impl HeadlessSink {
+    pub fn latest(&self) -> HeadlessFrame {
+        self.inner.lock().unwrap().clone()
+    }
+}
+
+impl FrameSink for HeadlessSink {
+    fn present(&mut self, emu: &Gbc<Mmu>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.fb.clone_from(&emu.cpu.ppu.fb);
+        inner.frames += 1;
+    }
+
+    fn set_status(&mut self, status: EmuStatus) {
+        self.inner.lock().unwrap().status = status;
+    }
+}
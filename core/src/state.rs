@@ -0,0 +1,28 @@
+#[derive(Clone, Debug)]
+pub struct StateDump {
+    pub next_instruction: gbc::Instruction,
+    pub regs: gbc::Registers,
+    pub io_regs: gbc::IoRegs,
+    pub memory: Vec<u8>,
+    /// Bank mapped into $4000-$7FFF
+    pub rom_bank: u16,
+    /// Both VRAM banks on CGB, just bank 0 on DMG
+    pub vram_banks: Vec<Vec<u8>>,
+    /// BG palette RAM then OBJ palette RAM, 64 bytes each, only on CGB
+    pub palette_ram: Option<Vec<u8>>,
+}
+
+impl StateDump {
+    /// 8 KiB of VRAM starting at $8000, falling back on the mapped bank in `memory` for bank 0
+    pub fn vram(&self, bank: usize) -> Option<&[u8]> {
+        match self.vram_banks.get(bank) {
+            Some(vram) => Some(vram),
+            None if bank == 0 => self.memory.get(0x8000..0xA000),
+            None => None,
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        self.palette_ram.is_some()
+    }
+}
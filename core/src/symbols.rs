@@ -0,0 +1,181 @@
+//! Labels from an RGBDS (or no$gmb) symbol file, loaded from `<rom>.sym`.
+//!
+//! Each line is `bank:addr label`, both in hex, and `;` starts a comment:
+//! ```text
+//! ; File generated by rgblink
+//! 00:0150 Main
+//! 01:4A10 Player.update
+//! ```
+
+use std::{collections::HashMap, fmt::Display, path::{Path, PathBuf}};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub bank: u16,
+    pub addr: u16,
+    pub name: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct SymbolError {
+    pub line: usize,
+    pub text: String,
+}
+
+impl Display for SymbolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: couldn't parse '{}'", self.line, self.text)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    by_addr: HashMap<u16, Vec<Symbol>>,
+    by_name: HashMap<String, Symbol>,
+}
+
+impl Symbols {
+    /// Bad lines are reported and skipped
+    pub fn parse(text: &str) -> (Self, Vec<SymbolError>) {
+        let mut symbols = Self::default();
+        let mut errors = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some(symbol) => symbols.insert(symbol),
+                None => errors.push(SymbolError { line: i + 1, text: line.to_owned() }),
+            }
+        }
+
+        (symbols, errors)
+    }
+
+    fn insert(&mut self, symbol: Symbol) {
+        self.by_name.insert(symbol.name.clone(), symbol.clone());
+        self.by_addr.entry(symbol.addr).or_default().push(symbol);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// The label at `addr`, preferring one in `bank` when several banks share the address
+    pub fn label(&self, addr: u16, bank: u16) -> Option<&str> {
+        let symbols = self.by_addr.get(&addr)?;
+
+        // only the switchable areas care which bank they're in
+        let banked = matches!(addr, 0x4000..=0x7FFF | 0xA000..=0xBFFF | 0xD000..=0xDFFF);
+
+        symbols.iter()
+            .find(|symbol| !banked || symbol.bank == bank)
+            .or_else(|| symbols.first())
+            .map(|symbol| symbol.name.as_str())
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).map(|symbol| symbol.addr)
+    }
+
+    /// Replaces every word in `text` that's a label with its address as `$XXXX`,
+    /// other than the ones `keep` says to leave alone
+    pub fn substitute(&self, text: &str, keep: impl Fn(&str) -> bool) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while !rest.is_empty() {
+            let len = rest.find(|c: char| !is_label_char(c)).unwrap_or(rest.len());
+
+            if len == 0 {
+                let c = rest.chars().next().unwrap();
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+
+            let (word, tail) = rest.split_at(len);
+
+            match self.resolve(word).filter(|_| !keep(word)) {
+                Some(addr) => out.push_str(&format!("${addr:04X}")),
+                None => out.push_str(word),
+            }
+
+            rest = tail;
+        }
+
+        out
+    }
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '@' | '#')
+}
+
+fn parse_line(line: &str) -> Option<Symbol> {
+    let (location, name) = line.split_once(char::is_whitespace)?;
+    let (bank, addr) = location.split_once(':')?;
+
+    Some(Symbol {
+        bank: u16::from_str_radix(bank, 16).ok()?,
+        addr: u16::from_str_radix(addr, 16).ok()?,
+        name: name.trim().to_owned(),
+    })
+}
+
+/// Symbols live next to the rom, as `<rom>.sym`
+pub fn path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("sym")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYM: &str = "\
+; File generated by rgblink
+00:0150 Main
+01:4A10 Player.update ; comment
+02:4A10 Enemy.update
+00:C000 wBuffer
+
+bad line
+";
+
+    #[test]
+    fn parse() {
+        let (symbols, errors) = Symbols::parse(SYM);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].text.as_str()), (7, "bad line"));
+        assert_eq!(symbols.resolve("Main"), Some(0x150));
+        assert_eq!(symbols.resolve("Player.update"), Some(0x4A10));
+        assert_eq!(symbols.resolve("main"), None);
+    }
+
+    #[test]
+    fn label_prefers_bank() {
+        let (symbols, _) = Symbols::parse(SYM);
+
+        assert_eq!(symbols.label(0x4A10, 2), Some("Enemy.update"));
+        assert_eq!(symbols.label(0x4A10, 1), Some("Player.update"));
+        // nothing in bank 5, so it falls back to the first one
+        assert_eq!(symbols.label(0x4A10, 5), Some("Player.update"));
+        // $C000 isn't banked, so the bank doesn't matter
+        assert_eq!(symbols.label(0xC000, 3), Some("wBuffer"));
+        assert_eq!(symbols.label(0x0151, 0), None);
+    }
+
+    #[test]
+    fn substitute() {
+        let (symbols, _) = Symbols::parse(SYM);
+
+        assert_eq!(symbols.substitute("pc == Main && [wBuffer] > 3", |_| false), "pc == $0150 && [$C000] > 3");
+        assert_eq!(symbols.substitute("Player.update+1", |_| false), "$4A10+1");
+        assert_eq!(symbols.substitute("Main", |word| word == "Main"), "Main");
+        assert_eq!(symbols.substitute("Mainly", |_| false), "Mainly");
+    }
+}
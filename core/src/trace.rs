@@ -0,0 +1,123 @@
+//! Instruction traces in the format used by Gameboy Doctor, one line per instruction
+//! with the state from just before it ran:
+//! ```text
+//! A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+//! ```
+//! Gameboy Doctor's reference logs were made with LY stuck at $90, so a [`Tracer`] with `pin_ly`
+//! set writes $90 to LY before every step to match them once a rom polls LY.
+
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path};
+
+use gbc::{memory::Memory, Gbc, Mmu};
+
+const LY: u16 = 0xFF44;
+
+/// Which instructions make it into the trace. Ranges are inclusive, and None lets everything through
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    pub frames: Option<(usize, usize)>,
+    pub pc: Option<(u16, u16)>,
+    /// Only instructions run from this ROM bank, with bank 0 being $0000-$3FFF
+    pub bank: Option<u16>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, frame: usize, pc: u16, rom_bank: u16) -> bool {
+        let frames = self.frames.is_none_or(|(start, end)| (start..=end).contains(&frame));
+        let pcs = self.pc.is_none_or(|(start, end)| (start..=end).contains(&pc));
+        let bank = self.bank.is_none_or(|bank| match pc {
+            0x0000..=0x3FFF => bank == 0,
+            0x4000..=0x7FFF => bank == rom_bank,
+            // running from RAM isn't in any ROM bank
+            _ => false,
+        });
+
+        frames && pcs && bank
+    }
+
+    /// Whether nothing past `frame` can match, so a headless run can stop
+    pub fn finished(&self, frame: usize) -> bool {
+        self.frames.is_some_and(|(_, end)| frame > end)
+    }
+}
+
+/// `start-end`, `start-` for no end, or a single value, in decimal
+pub fn parse_frames(text: &str) -> Option<(usize, usize)> {
+    parse_range(text, |value| value.parse().ok(), usize::MAX)
+}
+
+/// `start-end`, `start-` for no end, or a single address, in hex
+pub fn parse_pcs(text: &str) -> Option<(u16, u16)> {
+    parse_range(text, |value| u16::from_str_radix(value.trim_start_matches('$'), 16).ok(), u16::MAX)
+}
+
+fn parse_range<T: PartialOrd + Copy>(text: &str, parse: impl Fn(&str) -> Option<T>, max: T) -> Option<(T, T)> {
+    let (start, end) = match text.trim().split_once('-') {
+        Some((start, "")) => (parse(start.trim())?, max),
+        Some((start, end)) => (parse(start.trim())?, parse(end.trim())?),
+        None => {
+            let value = parse(text.trim())?;
+            (value, value)
+        },
+    };
+
+    (start <= end).then_some((start, end))
+}
+
+pub fn line(emu: &Gbc<Mmu>) -> String {
+    let regs = &emu.cpu.regs;
+    let mem = |offset: u16| emu.cpu.memory.load(regs.pc.wrapping_add(offset)).unwrap_or(0xFF);
+
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        regs.a, regs.f.as_byte(), regs.b, regs.c, regs.d, regs.e, regs.h, regs.l, regs.sp, regs.pc,
+        mem(0), mem(1), mem(2), mem(3),
+    )
+}
+
+pub struct Tracer {
+    writer: Box<dyn Write + Send>,
+    pub filter: TraceFilter,
+    /// Lines written so far
+    pub lines: u64,
+    /// Holds LY at $90 the way Gameboy Doctor does. The PPU still runs, so only use this to diff
+    /// against its logs
+    pub pin_ly: bool,
+}
+
+impl Tracer {
+    pub fn new(writer: impl Write + Send + 'static, filter: TraceFilter) -> Self {
+        Self {
+            writer: Box::new(writer),
+            filter,
+            lines: 0,
+            pin_ly: false,
+        }
+    }
+
+    pub fn create(path: impl AsRef<Path>, filter: TraceFilter) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), filter))
+    }
+
+    /// Writes the line for the instruction about to run, if the filter lets it through.
+    /// `rom_bank` is the bank mapped into $4000-$7FFF, as tracked by a [`crate::mbc::Mapper`].
+    /// Nothing runs while the CPU is halted, so those steps aren't written
+    pub fn trace(&mut self, emu: &mut Gbc<Mmu>, frame: usize, rom_bank: u16) -> io::Result<()> {
+        if self.pin_ly {
+            emu.cpu.memory.set(LY, 0x90);
+        }
+
+        if emu.cpu.halted || !self.filter.matches(frame, emu.cpu.regs.pc, rom_bank) {
+            return Ok(());
+        }
+
+        writeln!(self.writer, "{}", line(emu))?;
+        self.lines += 1;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,211 @@
+//! Decoding tiles and palettes out of VRAM for the debug viewers.
+//!
+//! Everything here works on one 8 KiB VRAM bank, indexed from $8000, and produces RGB888.
+//! The core only shows memory the way the CPU sees it, so the CGB's second bank and palette RAM
+//! are read by switching VBK and walking the palette index registers, see [`read_banks`].
+
+use gbc::memory::Memory;
+
+/// Tiles in one bank, from $8000 to $97FF
+pub const TILES: usize = 384;
+/// Where the two tile maps start, relative to $8000
+pub const MAP_0: usize = 0x1800;
+pub const MAP_1: usize = 0x1C00;
+
+pub type Rgb = [u8; 3];
+pub type Palette = [Rgb; 4];
+
+/// VBK, which maps VRAM bank 0 or 1 into $8000-$9FFF on CGB
+const VBK: u16 = 0xFF4F;
+/// BCPS and OCPS, which index into BG and OBJ palette RAM. The byte at the index is at the next address
+const PALETTE_INDEX: [u16; 2] = [0xFF68, 0xFF6A];
+/// Palette RAM for either the BG or OBJ, 8 palettes of 4 colors
+const PALETTE_RAM_LEN: u8 = 64;
+/// The CGB flag at $0143 in the header, which has bit 7 set for games that support CGB
+const CGB_FLAG: usize = 0x143;
+
+/// Shades the DMG palettes map to, from white to black
+pub const SHADES: Palette = [[0xFF, 0xFF, 0xFF], [0xAA, 0xAA, 0xAA], [0x55, 0x55, 0x55], [0x00, 0x00, 0x00]];
+
+/// What a DMG palette register like BGP or OBP0 maps each color to
+pub fn dmg_palette(reg: u8) -> Palette {
+    std::array::from_fn(|color| SHADES[(reg >> (color * 2) & 0b11) as usize])
+}
+
+/// Palette `index` out of 64 bytes of CGB palette RAM, which is 8 palettes of 4 little endian BGR555 colors
+pub fn cgb_palette(ram: &[u8], index: usize) -> Palette {
+    std::array::from_fn(|color| {
+        let offset = index * 8 + color * 2;
+        let value = u16::from_le_bytes([ram.get(offset).copied().unwrap_or(0), ram.get(offset + 1).copied().unwrap_or(0)]);
+        // 5 bits up to 8, filling the low bits so white stays white
+        let channel = |shift: u16| {
+            let c = (value >> shift & 0x1F) as u8;
+            c << 3 | c >> 2
+        };
+
+        [channel(0), channel(5), channel(10)]
+    })
+}
+
+/// Whether `rom` runs with the CGB's second VRAM bank and palette RAM
+pub fn is_cgb(rom: &[u8]) -> bool {
+    rom.get(CGB_FLAG).is_some_and(|flag| flag & 0x80 != 0)
+}
+
+/// Both VRAM banks on CGB, or just the one on DMG.
+/// This switches VBK to get at bank 1, and puts it back afterwards.
+pub fn read_banks(memory: &mut impl Memory, cgb: bool) -> Vec<Vec<u8>> {
+    if !cgb {
+        return vec![read_bank(memory)];
+    }
+
+    let vbk = memory.load(VBK).unwrap_or(0);
+    let banks = (0..2)
+        .map(|bank| {
+            memory.set(VBK, bank);
+            read_bank(memory)
+        })
+        .collect();
+
+    memory.set(VBK, vbk & 1);
+    banks
+}
+
+fn read_bank(memory: &impl Memory) -> Vec<u8> {
+    (0x8000..0xA000).map(|addr| memory.load(addr).unwrap_or(0xFF)).collect()
+}
+
+/// BG palette RAM then OBJ palette RAM, read a byte at a time by pointing BCPS or OCPS at it and
+/// reading BCPD or OCPD. The index registers are put back afterwards, auto-increment bit included.
+pub fn read_palette_ram(memory: &mut impl Memory) -> Vec<u8> {
+    let mut ram = Vec::with_capacity(PALETTE_RAM_LEN as usize * 2);
+
+    for index_reg in PALETTE_INDEX {
+        let index = memory.load(index_reg).unwrap_or(0);
+
+        for offset in 0..PALETTE_RAM_LEN {
+            memory.set(index_reg, offset);
+            ram.push(memory.load(index_reg + 1).unwrap_or(0xFF));
+        }
+
+        memory.set(index_reg, index);
+    }
+
+    ram
+}
+
+/// Address of tile `index` counting from $8000, the way the OBJ and LCDC.4 = 1 BG addressing does
+pub fn tile_addr(index: usize) -> u16 {
+    0x8000 + index as u16 * 16
+}
+
+/// Which of the 384 tiles a tile map entry points at, following LCDC.4
+pub fn map_tile(entry: u8, unsigned: bool) -> usize {
+    if unsigned || entry >= 0x80 {
+        entry as usize
+    } else {
+        // $8800 addressing puts 0-127 at $9000
+        entry as usize + 256
+    }
+}
+
+/// Color indices of tile `index`, one row of 8 pixels per entry
+pub fn decode_tile(bank: &[u8], index: usize) -> [[u8; 8]; 8] {
+    std::array::from_fn(|row| {
+        let offset = index * 16 + row * 2;
+        let lo = bank.get(offset).copied().unwrap_or(0);
+        let hi = bank.get(offset + 1).copied().unwrap_or(0);
+
+        std::array::from_fn(|x| (hi >> (7 - x) & 1) << 1 | lo >> (7 - x) & 1)
+    })
+}
+
+/// Draws tile `index` into an RGB image `width` pixels wide with its top left at `(x, y)`
+pub fn draw_tile(image: &mut [u8], width: usize, (x, y): (usize, usize), bank: &[u8], index: usize, palette: &Palette, flip: (bool, bool)) {
+    let pixels = decode_tile(bank, index);
+
+    for (row, line) in pixels.iter().enumerate() {
+        for (col, &color) in line.iter().enumerate() {
+            let px = x + if flip.0 { 7 - col } else { col };
+            let py = y + if flip.1 { 7 - row } else { row };
+            let offset = (py * width + px) * 3;
+
+            if let Some(out) = image.get_mut(offset..offset + 3) {
+                out.copy_from_slice(&palette[color as usize]);
+            }
+        }
+    }
+}
+
+/// Every tile in `bank`, `columns` tiles across, as an RGB image
+pub fn tile_sheet(bank: &[u8], columns: usize, palette: &Palette) -> ([usize; 2], Vec<u8>) {
+    let rows = TILES.div_ceil(columns);
+    let size = [columns * 8, rows * 8];
+    let mut image = vec![0; size[0] * size[1] * 3];
+
+    for index in 0..TILES {
+        draw_tile(&mut image, size[0], (index % columns * 8, index / columns * 8), bank, index, palette, (false, false));
+    }
+
+    (size, image)
+}
+
+/// One tile map entry, with CGB attributes from bank 1 if there are any
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapEntry {
+    /// Address of the entry in the map
+    pub addr: u16,
+    /// Which of the 384 tiles it uses
+    pub tile: usize,
+    /// Palette in bits 0-2, tile bank in 3, X/Y flip in 5/6 and priority in 7
+    pub attrs: u8,
+}
+
+impl MapEntry {
+    pub fn palette(&self) -> usize {
+        (self.attrs & 0b111) as usize
+    }
+
+    pub fn bank(&self) -> usize {
+        (self.attrs >> 3 & 1) as usize
+    }
+
+    pub fn flip(&self) -> (bool, bool) {
+        (self.attrs & 0x20 != 0, self.attrs & 0x40 != 0)
+    }
+
+    pub fn priority(&self) -> bool {
+        self.attrs & 0x80 != 0
+    }
+}
+
+/// Entry `(x, y)` in tiles of the map at `map`, which is [`MAP_0`] or [`MAP_1`].
+/// `banks` holds bank 0 and, on CGB, bank 1 with the attributes.
+pub fn map_entry(banks: &[&[u8]], map: usize, (x, y): (usize, usize), unsigned: bool) -> MapEntry {
+    let offset = map + (y % 32) * 32 + x % 32;
+    let index = banks[0].get(offset).copied().unwrap_or(0);
+    let attrs = banks.get(1).and_then(|bank| bank.get(offset)).copied().unwrap_or(0);
+
+    MapEntry {
+        addr: 0x8000 + offset as u16,
+        tile: map_tile(index, unsigned),
+        attrs,
+    }
+}
+
+/// The whole 256x256 map at `map` as an RGB image. On DMG `palettes` only needs BGP's.
+pub fn tile_map(banks: &[&[u8]], map: usize, unsigned: bool, palettes: &[Palette]) -> Vec<u8> {
+    let mut image = vec![0; 256 * 256 * 3];
+
+    for y in 0..32 {
+        for x in 0..32 {
+            let entry = map_entry(banks, map, (x, y), unsigned);
+            let bank = banks.get(entry.bank()).unwrap_or(&banks[0]);
+            let palette = palettes.get(entry.palette()).unwrap_or(&palettes[0]);
+
+            draw_tile(&mut image, 256, (x * 8, y * 8), bank, entry.tile, palette, entry.flip());
+        }
+    }
+
+    image
+}
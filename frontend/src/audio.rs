@@ -0,0 +1,110 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, FromSample, SampleFormat, SizedSample};
+use egui::mutex::Mutex;
+use gamboye_core::audio::AudioSink;
+
+/// Anything past this much buffered audio gets dropped, so the runner getting ahead of the device
+/// after a hitch doesn't build up latency
+const MAX_LATENCY_MS: u32 = 100;
+
+type SampleBuffer = Arc<Mutex<VecDeque<[f32; 2]>>>;
+
+#[derive(Debug)]
+pub enum AudioError {
+    NoDevice,
+    Config(cpal::DefaultStreamConfigError),
+    UnsupportedFormat(SampleFormat),
+    Build(cpal::BuildStreamError),
+    Play(cpal::PlayStreamError),
+}
+
+/// The host audio stream. This stays on the UI thread, and runners feed it through a [`CpalSink`]
+pub struct AudioOutput {
+    _stream: cpal::Stream,
+    buffer: SampleBuffer,
+    sample_rate: u32,
+}
+
+impl AudioOutput {
+    pub fn new() -> Result<Self, AudioError> {
+        let device = cpal::default_host().default_output_device().ok_or(AudioError::NoDevice)?;
+        let config = device.default_output_config().map_err(AudioError::Config)?;
+        let sample_rate = config.sample_rate().0;
+        let buffer: SampleBuffer = Default::default();
+
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config.config(), buffer.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config.config(), buffer.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config.config(), buffer.clone()),
+            format => return Err(AudioError::UnsupportedFormat(format)),
+        }.map_err(AudioError::Build)?;
+
+        stream.play().map_err(AudioError::Play)?;
+
+        Ok(Self {
+            _stream: stream,
+            buffer,
+            sample_rate,
+        })
+    }
+
+    pub fn sink(&self) -> CpalSink {
+        // a fresh runner shouldn't inherit the old one's leftovers
+        self.buffer.lock().clear();
+
+        CpalSink {
+            buffer: self.buffer.clone(),
+            sample_rate: self.sample_rate,
+            max_len: (self.sample_rate * MAX_LATENCY_MS / 1000) as usize,
+        }
+    }
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(device: &cpal::Device, config: &cpal::StreamConfig, buffer: SampleBuffer) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            let mut buffer = buffer.lock();
+
+            for frame in data.chunks_mut(channels) {
+                let [left, right] = buffer.pop_front().unwrap_or_default();
+
+                match frame {
+                    [mono] => *mono = T::from_sample((left + right) / 2.0),
+                    [l, r, rest @ ..] => {
+                        *l = T::from_sample(left);
+                        *r = T::from_sample(right);
+                        rest.fill(T::from_sample(0.0));
+                    },
+                    [] => {},
+                }
+            }
+        },
+        |err| eprintln!("Audio stream error: {err}"),
+        None,
+    )
+}
+
+/// Producer end of an [`AudioOutput`]
+pub struct CpalSink {
+    buffer: SampleBuffer,
+    sample_rate: u32,
+    max_len: usize,
+}
+
+impl AudioSink for CpalSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push(&mut self, samples: &[[f32; 2]]) {
+        let mut buffer = self.buffer.lock();
+        buffer.extend(samples);
+
+        let excess = buffer.len().saturating_sub(self.max_len);
+        buffer.drain(..excess);
+    }
+}
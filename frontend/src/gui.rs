@@ -1,76 +1,250 @@
+use std::{path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant}};
+
 use eframe::App;
 use egui::{pos2, Key, KeyboardShortcut, Modifiers, Pos2, ViewportId};
+use gamboye_core::breakpoints;
 use tokio::sync::mpsc;
 
-use crate::{comms::{self, EmuMsgOut}, runner::{Emu, EmuStatus}, state::{DebugState, EmuState, PerfState}};
+use crate::{comms::{self, EmuMsgIn, EmuMsgOut}, runner::{Emu, EmuStatus}, state::{AudioState, CheatsState, DebugState, DisasmState, EguiSink, EmuState, EventsState, FileState, InnerEmuState, IoState, OamState, PerfState, SearchState, SpeedState, StatusState, TraceState}};
 
 pub mod emu;
 pub mod perf;
 pub mod debug;
+pub mod disasm;
+pub mod file;
+pub mod search;
+pub mod cheats;
+pub mod tiles;
+pub mod maps;
+pub mod oam;
+pub mod io;
+pub mod events;
 
 pub const BASE_DISPLAY_POS: Pos2 = pos2(0.0, 0.0);
-// const MAX_FRAMERATE: usize = usize::MAX;
-const MAX_FRAMERATE: usize = 60;
+
+/// How long to wait for the runner to flush cartridge RAM when closing
+const EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a status message stays up
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
 
 const PERF_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, egui::Key::P);
 const DEBUG_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, egui::Key::D);
+const SLOW_MOTION_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::ALT, egui::Key::M);
 
 pub struct TopState {
     pub emu: EmuState,
     pub perf: PerfState,
     pub debug: DebugState,
+    pub file: FileState,
+    pub speed: SpeedState,
+    pub audio: AudioState,
+    pub search: SearchState,
+    pub cheats: CheatsState,
+    pub status: StatusState,
 }
 
 impl TopState {
-    pub fn new(cc: &eframe::CreationContext<'_>, rom: Vec<u8>) -> Self {
-        let ctx = cc.egui_ctx.clone();
-        let (ui_send, emu_recv) = mpsc::unbounded_channel();
-        let (emu_send, ui_recv) = mpsc::unbounded_channel();
-        let emu_state = EmuState::new(&cc.egui_ctx, ui_send, ui_recv);
+    pub fn new(cc: &eframe::CreationContext<'_>, rom: Option<(PathBuf, Vec<u8>)>) -> Self {
+        // no emu until a rom is loaded, so this receiver never gets anything
+        let (_, ui_recv) = mpsc::unbounded_channel();
+        let emu_state = EmuState::new(&cc.egui_ctx, None, ui_recv);
         let perf = Default::default();
-        
-        *emu_state.atoms.fb.lock() = vec![Default::default(); crate::runner::WIDTH * crate::runner::HEIGHT];
-
-        let mut emu = Emu::new(ctx, emu_recv, emu_send, emu_state.atoms.clone());
         let debug = DebugState {
             stopped: *emu_state.atoms.status.lock() == EmuStatus::Stopped,
             ..Default::default()
         };
-        
-        emu.init(&rom);
-        emu.run().unwrap();
 
-        Self {
+        let mut state = Self {
             emu: emu_state,
             perf,
             debug,
+            file: Default::default(),
+            speed: Default::default(),
+            audio: Default::default(),
+            search: Default::default(),
+            cheats: Default::default(),
+            status: Default::default(),
+        };
+
+        if let Some((path, rom)) = rom {
+            state.load_rom(&cc.egui_ctx, Some(path), &rom);
+        }
+
+        state
+    }
+
+    /// Tears down the running emu (if any) and starts a fresh one with `rom`.
+    /// Without a path nothing gets kept next to the rom, so there are no battery saves, states or movies
+    pub fn load_rom(&mut self, ctx: &egui::Context, path: Option<PathBuf>, rom: &[u8]) {
+        if let Some(sender) = self.emu.sender.take() {
+            // the old runner returns on this, and its channels are dropped along with it
+            let _ = sender.send(EmuMsgIn::LoadRom);
+        }
+
+        let (ui_send, emu_recv) = mpsc::unbounded_channel();
+        let (emu_send, ui_recv) = mpsc::unbounded_channel();
+        let atoms: Arc<InnerEmuState> = Default::default();
+
+        *atoms.fb.lock() = vec![Default::default(); crate::runner::WIDTH * crate::runner::HEIGHT];
+
+        let sink = EguiSink {
+            ctx: ctx.clone(),
+            atoms: atoms.clone(),
+        };
+        let mut emu = Emu::new(emu_recv, emu_send, sink);
+        if let Some(ref output) = self.audio.output {
+            emu.set_audio_sink(output.sink());
+        }
+        if let Some(previous) = self.emu.task.take() {
+            emu.after(previous);
+        }
+        emu.init(rom, path.clone());
+        self.emu.task = Some(emu.run().unwrap());
+
+        // carry the speed and audio settings over to the new runner
+        ui_send.send(EmuMsgIn::SetSpeed(self.speed.percent as f64 / 100.0)).unwrap();
+        ui_send.send(EmuMsgIn::SlowMotion(self.speed.slow_motion)).unwrap();
+        ui_send.send(EmuMsgIn::SetVolume(self.audio.volume)).unwrap();
+        ui_send.send(EmuMsgIn::SetMuted(self.audio.muted)).unwrap();
+
+        self.emu.atoms = atoms;
+        self.emu.sender = Some(ui_send);
+        self.emu.receiver = ui_recv;
+        self.emu.rom_path = path.clone();
+
+        self.file.movie = None;
+
+        // freezes died with the old runner
+        self.search = SearchState {
+            open: self.search.open,
+            width: self.search.width,
+            ..Default::default()
+        };
+
+        // the new emu starts running with no breakpoints set, other than the ones saved for this rom
+        self.debug = DebugState {
+            open: self.debug.open,
+            disasm: DisasmState {
+                open: self.debug.disasm.open,
+                ..Default::default()
+            },
+            // viewer settings aren't specific to a rom
+            tiles: self.debug.tiles.clone(),
+            maps: self.debug.maps.clone(),
+            oam: OamState {
+                open: self.debug.oam.open,
+                ..Default::default()
+            },
+            // the new runner starts with logging off
+            events: EventsState {
+                open: self.debug.events.open,
+                hidden: self.debug.events.hidden.clone(),
+                ..Default::default()
+            },
+            io: IoState {
+                open: self.debug.io.open,
+                decode: self.debug.io.decode,
+                ..Default::default()
+            },
+            breakpoints_path: path.as_deref().map(breakpoints::path),
+            trace: TraceState {
+                path: path.as_ref().map(|path| path.with_extension("trace").to_string_lossy().into_owned()).unwrap_or_default(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        if let Some(ref path) = path {
+            debug::load_symbols(&mut self.debug, path);
+        }
+
+        self.cheats = CheatsState {
+            open: self.cheats.open,
+            ..Default::default()
+        };
+
+        if let Some(ref sender) = self.emu.sender {
+            debug::load_breakpoints(&mut self.debug, sender);
+
+            if let Some(ref path) = path {
+                cheats::load(&mut self.cheats, path, sender);
+            }
         }
+
+        let title = path.as_deref().and_then(Path::file_name).map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        ctx.send_viewport_cmd(egui::ViewportCommand::Title(format!("Beef Wellington - {title}")));
     }
 }
 
 impl App for TopState {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if let Ok(msg) = self.emu.receiver.try_recv() {
+        while let Ok(msg) = self.emu.receiver.try_recv() {
             match msg {
                 EmuMsgOut::State(state) => {
+                    if let Some(ref old) = self.debug.emu_state {
+                        io::record(&mut self.debug.io, old, &state);
+                    }
+
                     self.debug.emu_state = Some(state);
-                }
+                    self.debug.disasm.starts_anchor = None;
+                },
+                EmuMsgOut::Exited => {},
+                EmuMsgOut::Movie(status) => {
+                    self.file.movie = status;
+                },
+                EmuMsgOut::BreakpointHit(breakpoint) => {
+                    self.debug.breakpoints.list.hit(breakpoint);
+                },
+                EmuMsgOut::ConditionHit(id) => {
+                    self.debug.breakpoints.list.condition_hit(id);
+                },
+                EmuMsgOut::Events(batch) => {
+                    events::record(&mut self.debug.events, batch);
+                },
+                EmuMsgOut::Status(text) => {
+                    self.status = StatusState { text, error: false, shown: Some(Instant::now()) };
+                },
+                EmuMsgOut::Error(text) => {
+                    self.status = StatusState { text, error: true, shown: Some(Instant::now()) };
+                },
             }
         }
 
+        file::handle_dropped_files(ctx, self);
+        file::handle_shortcuts(ctx, self);
+
         egui::TopBottomPanel::top("main_menubar").show(ctx, |ui| {
-            ui.menu_button("View", |ui| {
-                ui.checkbox(&mut self.perf.open, "Performance");
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    file::menu(ui, self);
+                });
 
-                if ui.checkbox(&mut self.debug.open, "Debug").changed() {
-                    if self.debug.open {
-                        self.debug.vram = Some(debug::load_vram_texture(ctx, &*self.emu.atoms.vram.lock()));
-                    }
-                }
+                ui.menu_button("Speed", |ui| {
+                    speed_menu(ui, self);
+                });
 
-                ()
+                ui.menu_button("View", |ui| {
+                    ui.checkbox(&mut self.perf.open, "Performance");
+                    ui.checkbox(&mut self.debug.disasm.open, "Disassembly");
+                    ui.checkbox(&mut self.search.open, "Memory Search");
+                    ui.checkbox(&mut self.cheats.open, "Cheats");
+
+                    ui.checkbox(&mut self.debug.tiles.open, "Tiles");
+                    ui.checkbox(&mut self.debug.maps.open, "Tile Maps");
+                    ui.checkbox(&mut self.debug.oam.open, "OAM");
+                    ui.checkbox(&mut self.debug.io.open, "IO Registers");
+                    ui.checkbox(&mut self.debug.events.open, "Events");
+                    ui.checkbox(&mut self.debug.open, "Debug");
+
+                    ui.separator();
+                    audio_controls(ui, self);
+
+                    ()
+                });
             });
         });
+
+        status_bar(ctx, &mut self.status);
             
         if self.perf.open {
             perf::show(ctx, &mut self.perf);
@@ -78,10 +252,6 @@ impl App for TopState {
 
         if ctx.input_mut(|i| i.consume_shortcut(&DEBUG_SHORTCUT)) {
             self.debug.open = !self.debug.open;
-
-            if self.debug.open {
-                self.debug.vram = Some(debug::load_vram_texture(ctx, &*self.emu.atoms.vram.lock()));
-            }
         }
 
         if self.debug.open {
@@ -90,6 +260,48 @@ impl App for TopState {
             }
         }
 
+        if self.debug.disasm.open {
+            if let Some(ref sender) = self.emu.sender {
+                disasm::show(ctx, &mut self.debug, sender);
+            }
+        }
+
+        if self.debug.tiles.open {
+            tiles::show(ctx, &mut self.debug);
+        }
+
+        if self.debug.maps.open {
+            maps::show(ctx, &mut self.debug);
+        }
+
+        if self.debug.events.open {
+            if let Some(ref sender) = self.emu.sender {
+                events::show(ctx, &mut self.debug, sender);
+            }
+        }
+
+        if self.debug.io.open {
+            io::show(ctx, &mut self.debug);
+        }
+
+        if self.debug.oam.open {
+            oam::show(ctx, &mut self.debug);
+        } else {
+            self.debug.oam.hovered = None;
+        }
+
+        if self.search.open {
+            if let Some(ref sender) = self.emu.sender {
+                search::show(ctx, &mut self.search, &self.debug, sender);
+            }
+        }
+
+        if self.cheats.open {
+            if let Some(ref sender) = self.emu.sender {
+                cheats::show(ctx, &mut self.cheats, sender);
+            }
+        }
+
         let res = emu::show(ctx, self);
 
         if res.response.rect != self.emu.display_rect {
@@ -99,5 +311,106 @@ impl App for TopState {
         if ctx.input_mut(|i| i.consume_shortcut(&PERF_SHORTCUT)) {
             self.perf.open = !self.perf.open;
         }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&SLOW_MOTION_SHORTCUT)) {
+            self.speed.slow_motion = !self.speed.slow_motion;
+
+            if let Some(ref sender) = self.emu.sender {
+                sender.send(EmuMsgIn::SlowMotion(self.speed.slow_motion)).unwrap();
+            }
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let Some(sender) = self.emu.sender.take() else {
+            return;
+        };
+
+        if sender.send(EmuMsgIn::Exit).is_err() {
+            return;
+        }
+
+        // we're inside the tokio runtime here, so blocking_recv isn't an option
+        let deadline = Instant::now() + EXIT_TIMEOUT;
+        while Instant::now() < deadline {
+            match self.emu.receiver.try_recv() {
+                Ok(EmuMsgOut::Exited)
+                | Err(mpsc::error::TryRecvError::Disconnected) => return,
+                Ok(_) => {},
+                Err(mpsc::error::TryRecvError::Empty) => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+
+        eprintln!("Timed out waiting for the emulator to exit");
+    }
+}
+fn status_bar(ctx: &egui::Context, status: &mut StatusState) {
+    let Some(shown) = status.shown else {
+        return;
+    };
+
+    let left = STATUS_TIMEOUT.saturating_sub(shown.elapsed());
+    if left.is_zero() {
+        status.shown = None;
+        return;
+    }
+
+    egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+        if status.error {
+            ui.colored_label(ui.visuals().error_fg_color, &status.text);
+        } else {
+            ui.label(&status.text);
+        }
+    });
+
+    // nothing else might redraw once the emu is paused
+    ctx.request_repaint_after(left);
+}
+
+fn speed_menu(ui: &mut egui::Ui, state: &mut TopState) {
+    let min = (crate::pacing::MIN_SPEED * 100.0) as u32;
+    let max = (crate::pacing::MAX_SPEED * 100.0) as u32;
+
+    let speed = ui.add(egui::Slider::new(&mut state.speed.percent, min..=max).logarithmic(true).suffix("%"));
+    let reset = ui.button("Normal speed").clicked();
+    let slow_motion = ui.checkbox(&mut state.speed.slow_motion, "Slow motion (Alt+M)");
+    ui.label("Hold T for turbo");
+
+    if reset {
+        state.speed.percent = 100;
+    }
+
+    let Some(ref sender) = state.emu.sender else {
+        return;
+    };
+
+    if speed.changed() || reset {
+        sender.send(EmuMsgIn::SetSpeed(state.speed.percent as f64 / 100.0)).unwrap();
+    }
+
+    if slow_motion.changed() {
+        sender.send(EmuMsgIn::SlowMotion(state.speed.slow_motion)).unwrap();
+    }
+}
+
+fn audio_controls(ui: &mut egui::Ui, state: &mut TopState) {
+    if state.audio.output.is_none() {
+        ui.label("No audio device");
+        return;
     }
-}
\ No newline at end of file
+
+    let volume = ui.add(egui::Slider::new(&mut state.audio.volume, 0.0..=1.0).text("Volume").custom_formatter(|v, _| format!("{:.0}%", v * 100.0)));
+    let muted = ui.checkbox(&mut state.audio.muted, "Mute");
+
+    let Some(ref sender) = state.emu.sender else {
+        return;
+    };
+
+    if volume.changed() {
+        sender.send(EmuMsgIn::SetVolume(state.audio.volume)).unwrap();
+    }
+
+    if muted.changed() {
+        sender.send(EmuMsgIn::SetMuted(state.audio.muted)).unwrap();
+    }
+}
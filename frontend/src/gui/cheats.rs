@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use egui::{Color32, Context, RichText};
+use gamboye_core::cheats::{self, Cheat};
+use tokio::sync::mpsc;
+
+use crate::{comms::EmuMsgIn, state::CheatsState};
+
+pub fn show(ctx: &Context, state: &mut CheatsState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let mut open = state.open;
+    let mut changed = false;
+
+    egui::Window::new("Cheats").open(&mut open).default_width(300.0).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.code).hint_text("code").desired_width(120.0));
+            ui.add(egui::TextEdit::singleline(&mut state.label).hint_text("label").desired_width(100.0));
+
+            if ui.button("Add").clicked() {
+                match Cheat::new(state.label.trim().to_owned(), &state.code) {
+                    Ok(cheat) => {
+                        state.list.entries.push(cheat);
+                        state.code.clear();
+                        state.label.clear();
+                        state.error = None;
+                        changed = true;
+                    },
+                    Err(err) => state.error = Some(err),
+                }
+            }
+        });
+
+        ui.weak("Game Genie ABC-DEF(-GHI) or GameShark ABCDEFGH, several separated by spaces");
+
+        if let Some(ref error) = state.error {
+            ui.colored_label(Color32::RED, error);
+        }
+
+        ui.separator();
+
+        if state.list.entries.is_empty() {
+            ui.weak("No cheats for this rom");
+            return;
+        }
+
+        let mut removed = None;
+
+        egui::Grid::new("cheat_list").striped(true).show(ui, |ui| {
+            for (i, cheat) in state.list.entries.iter_mut().enumerate() {
+                changed |= ui.checkbox(&mut cheat.enabled, "").changed();
+
+                let label = if cheat.label.is_empty() { "(no label)" } else { &cheat.label };
+                let decoded = cheat.codes.iter().map(|code| code.to_string()).collect::<Vec<_>>().join("\n");
+                ui.label(label).on_hover_text(decoded);
+                ui.label(RichText::new(&cheat.text).monospace());
+
+                if ui.small_button("x").clicked() {
+                    removed = Some(i);
+                }
+
+                ui.end_row();
+            }
+        });
+
+        if let Some(i) = removed {
+            state.list.entries.remove(i);
+            changed = true;
+        }
+    });
+
+    if changed {
+        sender.send(EmuMsgIn::SetCheats(state.list.active())).unwrap();
+        save(state);
+    }
+
+    state.open = open;
+}
+
+/// Loads the cheats saved for `rom_path` and turns the enabled ones on
+pub fn load(state: &mut CheatsState, rom_path: &Path, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let path = cheats::path(rom_path);
+
+    if let Ok(text) = std::fs::read_to_string(&path) {
+        let (list, errors) = cheats::decode(&text);
+
+        for error in errors {
+            eprintln!("Skipping cheat in {}: {error}", path.display());
+        }
+
+        sender.send(EmuMsgIn::SetCheats(list.active())).unwrap();
+        state.list = list;
+    }
+
+    state.path = Some(path);
+}
+
+fn save(state: &CheatsState) {
+    let Some(ref path) = state.path else {
+        return;
+    };
+
+    // don't leave empty files lying around next to every rom
+    let res = if state.list.entries.is_empty() {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    } else {
+        std::fs::write(path, cheats::encode(&state.list))
+    };
+
+    if let Err(err) = res {
+        eprintln!("Couldn't save cheats to {}: {err}", path.display());
+    }
+}
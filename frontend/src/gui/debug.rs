@@ -1,7 +1,10 @@
-use egui::{load::SizedTexture, ColorImage, Context, RichText, TextureHandle, TextureOptions};
+use std::path::{Path, PathBuf};
+
+use egui::{Context, RichText};
+use gamboye_core::{breakpoints::{self, Trigger}, expr, symbols::{self, Symbols}, trace::{self, TraceFilter}};
 use tokio::sync::mpsc;
 
-use crate::{comms::EmuMsgIn, runner::Breakpoint, state::DebugState};
+use crate::{comms::EmuMsgIn, runner::{Breakpoint, EmuStatus}, state::{DebugState, TraceState}};
 
 pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
     egui::SidePanel::left("debug").resizable(false).show(ctx, |ui| {
@@ -25,61 +28,43 @@ pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSende
                 if ui.button("Step").clicked() {
                     sender.send(EmuMsgIn::Step(1)).unwrap();
                 }
+
+                if ui.button("Frame").on_hover_text("Run to the next frame (F)").clicked() {
+                    state.stopped = true;
+                    sender.send(EmuMsgIn::FrameAdvance).unwrap();
+                }
             });
 
-            ui.menu_button("Breakpoints", |ui| {
-                ui.horizontal(|ui| {
-                    ui.vertical(|ui| {
-                        breakpoint_toggle(ui, &mut state.breakpoints.a_reg, "A", Breakpoint::A, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.b_reg, "B", Breakpoint::B, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.c_reg, "C", Breakpoint::C, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.d_reg, "D", Breakpoint::D, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.h_reg, "H", Breakpoint::H, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.l_reg, "L", Breakpoint::L, sender);
-                    });
-                    ui.vertical(|ui| {
-                        breakpoint_toggle(ui, &mut state.breakpoints.zero_flag, "Z", Breakpoint::Zero, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.subtract_flag, "N", Breakpoint::Subtract, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.half_carry_flag, "H", Breakpoint::HalfCarry, sender);
-                        breakpoint_toggle(ui, &mut state.breakpoints.carry_flag, "C", Breakpoint::Carry, sender);
-                    });
-                });
-                
-                ui.separator();
-                ui.label(RichText::new("Memory Set").strong().monospace());
-                ui.horizontal(|ui| {
-                    if ui.checkbox(&mut state.breakpoints.mem_write.1, "Set").changed() {
-                        if let Ok(addr) = u16::from_str_radix(&state.breakpoints.mem_write.0, 16) {
-                            let breakpoint = Breakpoint::MemoryWrite(addr);
-                            if state.breakpoints.mem_write.1 {
-                                sender.send(EmuMsgIn::SetBreakpoint(breakpoint)).unwrap();
-                            } else {
-                                sender.send(EmuMsgIn::UnsetBreakpoint(breakpoint)).unwrap();
-                            }
-                        }
-                    }
+            ui.horizontal(|ui| {
+                if ui.button("Step Over").clicked() {
+                    state.stopped = true;
+                    sender.send(EmuMsgIn::StepOver).unwrap();
+                }
+
+                if ui.button("Step Out").clicked() {
+                    state.stopped = true;
+                    sender.send(EmuMsgIn::StepOut).unwrap();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                let addr = parse_addr(state, &state.run_to);
 
-                    ui.text_edit_singleline(&mut state.breakpoints.mem_write.0).changed();
+                if ui.add_enabled(addr.is_some(), egui::Button::new("Run To")).clicked() {
+                    state.stopped = true;
+                    sender.send(EmuMsgIn::RunTo(addr.unwrap())).unwrap();
+                }
+
+                ui.add(egui::TextEdit::singleline(&mut state.run_to).hint_text("addr or label").desired_width(96.0));
+            });
+
+            ui.horizontal(|ui| {
+                ui.menu_button("Breakpoints", |ui| {
+                    breakpoint_list(ui, state, sender);
                 });
-                
-                ui.separator();
-                ui.label(RichText::new("PC").strong().monospace());
-                ui.horizontal(|ui| {
-                    if ui.checkbox(&mut state.breakpoints.pc.1, "Set").changed() {
-                        if let Ok(addr) = u16::from_str_radix(&state.breakpoints.pc.0, 16) {
-                            let breakpoint = Breakpoint::Pc(addr);
-                            if state.breakpoints.pc.1 {
-                                sender.send(EmuMsgIn::SetBreakpoint(breakpoint)).unwrap();
-                            } else {
-                                state.breakpoints.mem_write.0.clear();
-                                sender.send(EmuMsgIn::UnsetBreakpoint(breakpoint)).unwrap();
-                            }
-                        }
-                    }
 
-                    if ui.text_edit_singleline(&mut state.breakpoints.pc.0).changed() {
-                        state.breakpoints.pc.1 = false;
-                    }
+                ui.menu_button(if state.trace.active { "Trace (on)" } else { "Trace" }, |ui| {
+                    trace_menu(ui, state, sender);
                 });
             });
 
@@ -88,15 +73,21 @@ pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSende
 
             ui.strong("Emu Status");
             ui.label(format!("{}", state.emu_status));
-            
-            if let Some(ref vram) = state.vram {
-                ui.strong("VRAM");
-                ui.image(SizedTexture::from_handle(vram));
-            }
 
             ui.vertical(|ui| {
                 ui.strong("Registers");
-                show_reg_hex_word(ui, "PC", state.emu_state.as_ref().map(|s| s.regs.pc).unwrap_or(0));
+
+                // the runner would overwrite edits straight away while it's running
+                if matches!(state.emu_status, EmuStatus::Stopped | EmuStatus::Break) && state.emu_state.is_some() {
+                    register_editor(ui, state, sender);
+                    return;
+                }
+
+                let pc = state.emu_state.as_ref().map(|s| s.regs.pc).unwrap_or(0);
+                match label(state, pc) {
+                    Some(label) => show_reg(ui, "PC", &format!("{pc:#06X} {label}")),
+                    None => show_reg_hex_word(ui, "PC", pc),
+                }
                 show_reg_hex_word(ui, "SP", state.emu_state.as_ref().map(|s| s.regs.sp).unwrap_or(0));
                 show_reg_hex(ui, "A ", state.emu_state.as_ref().map(|s| s.regs.a).unwrap_or(0));
                 show_reg_hex(ui, "B ", state.emu_state.as_ref().map(|s| s.regs.b).unwrap_or(0));
@@ -105,14 +96,21 @@ pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSende
                 show_reg_hex(ui, "E ", state.emu_state.as_ref().map(|s| s.regs.e).unwrap_or(0));
                 show_reg_hex(ui, "H ", state.emu_state.as_ref().map(|s| s.regs.h).unwrap_or(0));
                 show_reg_hex(ui, "L ", state.emu_state.as_ref().map(|s| s.regs.l).unwrap_or(0));
+                show_reg(ui, "F ", &flags_text(state.emu_state.as_ref().map(|s| s.regs.f.as_byte()).unwrap_or(0)));
             });
 
             ui.vertical(|ui| {
-                ui.strong("IO Registers");
+                ui.horizontal(|ui| {
+                    ui.strong("IO Registers");
+
+                    if ui.small_button("All").on_hover_text("Open the IO register inspector").clicked() {
+                        state.io.open = true;
+                    }
+                });
                 show_reg_bin(ui, "LCDC", state.emu_state.as_ref().map(|s| s.io_regs.lcdc).unwrap_or(0));
                 show_reg_bin(ui, "JOYP", state.emu_state.as_ref().map(|s| s.io_regs.joyp).unwrap_or(0));
-                show_reg_dec(ui, "SCY ", state.emu_state.as_ref().map(|s| s.io_regs.scx).unwrap_or(0));
-                show_reg_dec(ui, "SCX ", state.emu_state.as_ref().map(|s| s.io_regs.scy).unwrap_or(0));
+                show_reg_dec(ui, "SCY ", state.emu_state.as_ref().map(|s| s.io_regs.scy).unwrap_or(0));
+                show_reg_dec(ui, "SCX ", state.emu_state.as_ref().map(|s| s.io_regs.scx).unwrap_or(0));
                 show_reg_bin(ui, "STAT", state.emu_state.as_ref().map(|s| s.io_regs.stat).unwrap_or(0));
                 show_reg_dec(ui, "LYC ", state.emu_state.as_ref().map(|s| s.io_regs.lyc).unwrap_or(0));
                 show_reg_dec(ui, "LY  ", state.emu_state.as_ref().map(|s| s.io_regs.ly).unwrap_or(0));
@@ -124,6 +122,11 @@ pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSende
         let text_style = egui::TextStyle::Body;
         let row_height = ui.text_style_height(&text_style);
 
+        // taken out so it can be edited while the memory is borrowed for display
+        let mut edit = state.mem_edit.take();
+        let mut write = None;
+        let edit_id = egui::Id::new("debug-memory-edit");
+
         egui::ScrollArea::vertical().show_rows(ui, row_height, (u16::MAX / 16).into(), |ui, row_range| {
             if let Some(memory) = state.emu_state.as_ref().map(|s| &s.memory) {
                 for row in row_range {
@@ -134,8 +137,48 @@ pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSende
                         ui.label(RichText::new(format!("${y:04X}")).strong().monospace());
 
                         for x in 0..16 {
-                            let current = memory[y as usize + x as usize];
-                            ui.monospace(format!("{current:02X}"));
+                            let addr = y + x;
+                            let current = memory[addr];
+
+                            if let Some((edit_addr, ref mut text)) = edit {
+                                if edit_addr as usize == addr {
+                                    let response = ui.add(egui::TextEdit::singleline(text).id(edit_id).char_limit(2).desired_width(16.0).font(egui::TextStyle::Monospace));
+
+                                    let parsed = || u8::from_str_radix(text, 16).ok();
+
+                                    // a full byte moves on to the next one, Enter finishes a short one,
+                                    // and clicking away or Escape leaves the byte as it was
+                                    if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                                        write = Some(None);
+                                    } else if text.len() == 2 {
+                                        write = Some(parsed().map(|value| (edit_addr, value, true)));
+                                    } else if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                                        write = Some(parsed().map(|value| (edit_addr, value, false)));
+                                    } else if response.lost_focus() {
+                                        write = Some(None);
+                                    }
+
+                                    continue;
+                                }
+                            }
+
+                            let mut text = RichText::new(format!("{current:02X}")).monospace();
+                            let label = label(state, addr as u16);
+
+                            if label.is_some() {
+                                text = text.underline();
+                            }
+
+                            let mut response = ui.add(egui::Label::new(text).sense(egui::Sense::click()));
+
+                            if let Some(label) = label {
+                                response = response.on_hover_text(label);
+                            }
+
+                            if response.clicked() {
+                                edit = Some((addr as u16, String::new()));
+                                ui.memory_mut(|memory| memory.request_focus(edit_id));
+                            }
                         }
                         
                         ui.add_space(2.0);
@@ -143,9 +186,108 @@ pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSende
                 }
             }
         });
+
+        match write {
+            Some(Some((addr, value, advance))) => {
+                sender.send(EmuMsgIn::WriteMemory { addr, bytes: vec![value] }).unwrap();
+
+                if let Some(memory) = state.emu_state.as_mut().map(|s| &mut s.memory) {
+                    if let Some(byte) = memory.get_mut(addr as usize) {
+                        *byte = value;
+                    }
+                }
+
+                // moving on keeps focus in the field, so runs of bytes can be typed straight in
+                if advance {
+                    state.mem_edit = Some((addr.wrapping_add(1), String::new()));
+                    ui.memory_mut(|memory| memory.request_focus(edit_id));
+                } else {
+                    state.mem_edit = None;
+                }
+            },
+            Some(None) => state.mem_edit = None,
+            None => state.mem_edit = edit,
+        }
     });
 }
 
+/// Flags in F that can be edited, as (bit, name)
+const FLAGS: [(u8, &str); 4] = [(7, "Z"), (6, "N"), (5, "H"), (4, "C")];
+
+fn flags_text(f: u8) -> String {
+    FLAGS.iter().map(|&(bit, name)| if f >> bit & 1 == 1 { name } else { "-" }).collect()
+}
+
+fn register_editor(ui: &mut egui::Ui, state: &mut DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let Some(pc) = state.emu_state.as_ref().map(|s| s.regs.pc) else {
+        return;
+    };
+
+    let pc_label = label(state, pc).map(str::to_owned);
+    let Some(emu_state) = state.emu_state.as_mut() else {
+        return;
+    };
+
+    let mut regs = emu_state.regs;
+    let mut f = regs.f.as_byte();
+    let mut changed = false;
+
+    changed |= edit_reg_hex_word(ui, "PC", &mut regs.pc, pc_label.as_deref());
+    changed |= edit_reg_hex_word(ui, "SP", &mut regs.sp, None);
+    changed |= edit_reg_hex(ui, "A ", &mut regs.a);
+    changed |= edit_reg_hex(ui, "B ", &mut regs.b);
+    changed |= edit_reg_hex(ui, "C ", &mut regs.c);
+    changed |= edit_reg_hex(ui, "D ", &mut regs.d);
+    changed |= edit_reg_hex(ui, "E ", &mut regs.e);
+    changed |= edit_reg_hex(ui, "H ", &mut regs.h);
+    changed |= edit_reg_hex(ui, "L ", &mut regs.l);
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.monospace("F ");
+        ui.separator();
+
+        for (bit, name) in FLAGS {
+            let mut set = f >> bit & 1 == 1;
+
+            if ui.checkbox(&mut set, name).changed() {
+                f ^= 1 << bit;
+                changed = true;
+            }
+        }
+    });
+
+    if changed {
+        regs.f.set_bits(f);
+        emu_state.regs = regs;
+        sender.send(EmuMsgIn::SetRegisters(regs)).unwrap();
+    }
+}
+
+fn edit_reg_hex(ui: &mut egui::Ui, name: &str, value: &mut u8) -> bool {
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.monospace(name);
+        ui.separator();
+        ui.add(egui::DragValue::new(value).hexadecimal(2, false, true).prefix("0x")).changed()
+    }).inner
+}
+
+fn edit_reg_hex_word(ui: &mut egui::Ui, name: &str, value: &mut u16, label: Option<&str>) -> bool {
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.monospace(name);
+        ui.separator();
+        let changed = ui.add(egui::DragValue::new(value).hexadecimal(4, false, true).prefix("0x")).changed();
+
+        if let Some(label) = label {
+            ui.monospace(label);
+        }
+
+        changed
+    }).inner
+}
+
 fn show_reg_dec(ui: &mut egui::Ui, name: &str, value: u8) {
     show_reg(ui, name, &value.to_string())
 }
@@ -171,20 +313,267 @@ fn show_reg(ui: &mut egui::Ui, name: &str, text: &str) {
     });
 }
 
-fn breakpoint_toggle(ui: &mut egui::Ui, value: &mut bool, text: &str, breakpoint: Breakpoint, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
-    if ui.checkbox(value, text).changed() {
-        if *value {
-            sender.send(EmuMsgIn::SetBreakpoint(breakpoint)).unwrap();
-        } else {
-            sender.send(EmuMsgIn::UnsetBreakpoint(breakpoint)).unwrap();
+/// Kinds of breakpoint that can be added, as (trigger keyword, name, hint)
+const BREAKPOINT_KINDS: [(&str, &str, &str); 6] = [
+    ("pc", "PC", "0150 or label"),
+    ("read", "Read", "C000 or FE00-FE9F"),
+    ("write", "Write", "C000, FE00-FE9F or C0A0=3F/F0"),
+    ("reg", "Register", "a"),
+    ("flag", "Flag", "z"),
+    ("if", "Condition", "pc == $4A10 && ly == 144"),
+];
+
+fn breakpoint_list(ui: &mut egui::Ui, state: &mut DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let bank = state.emu_state.as_ref().map(|s| s.rom_bank).unwrap_or(1);
+    let symbols = &state.symbols;
+    let breakpoints = &mut state.breakpoints;
+    let mut changed = false;
+    let mut removed = None;
+
+    egui::Grid::new("breakpoint_list").striped(true).show(ui, |ui| {
+        for entry in &mut breakpoints.list.entries {
+            if ui.checkbox(&mut entry.enabled, "").changed() {
+                let msg = if entry.enabled { entry.set_msg() } else { entry.unset_msg() };
+                sender.send(msg).unwrap();
+                changed = true;
+            }
+
+            changed |= ui.add(egui::TextEdit::singleline(&mut entry.label).hint_text("label").desired_width(96.0)).lost_focus();
+            let addr = match entry.trigger {
+                Trigger::Cpu(Breakpoint::Pc(addr) | Breakpoint::MemoryRead(addr) | Breakpoint::MemoryWrite(addr)) => Some(addr),
+                Trigger::Cpu(Breakpoint::WriteValue { addr, .. }) => Some(addr),
+                _ => None,
+            };
+
+            match addr.and_then(|addr| symbols.label(addr, bank)) {
+                Some(label) => ui.monospace(format!("{} ({label})", entry.trigger)),
+                None => ui.monospace(entry.trigger.to_string()),
+            };
+            ui.label(format!("{} hits", entry.hits));
+
+            if ui.small_button("x").clicked() {
+                removed = Some(entry.id);
+            }
+
+            ui.end_row();
+        }
+    });
+
+    if let Some(entry) = removed.and_then(|id| breakpoints.list.remove(id)) {
+        if entry.enabled {
+            sender.send(entry.unset_msg()).unwrap();
+        }
+
+        changed = true;
+    }
+
+    ui.separator();
+
+    let (keyword, _, hint) = BREAKPOINT_KINDS[breakpoints.kind];
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source("breakpoint_kind")
+            .selected_text(BREAKPOINT_KINDS[breakpoints.kind].1)
+            .show_ui(ui, |ui| {
+                for (i, (_, name, _)) in BREAKPOINT_KINDS.iter().enumerate() {
+                    ui.selectable_value(&mut breakpoints.kind, i, *name);
+                }
+            });
+
+        ui.add(egui::TextEdit::singleline(&mut breakpoints.arg).hint_text(hint).code_editor());
+
+        if keyword == "if" {
+            ui.add(egui::DragValue::new(&mut breakpoints.hit_count).clamp_range(1..=u32::MAX).prefix("hits: "));
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(&mut breakpoints.label).hint_text("label"));
+
+        if ui.button("Add").clicked() {
+            // labels can go anywhere an address can, other than where they'd shadow a register or a number
+            let text = if keyword == "if" {
+                let arg = symbols.substitute(&breakpoints.arg, expr::is_var);
+                format!("if {} {arg}", breakpoints.hit_count.max(1))
+            } else {
+                let arg = symbols.substitute(&breakpoints.arg, |word| u16::from_str_radix(word, 16).is_ok());
+                format!("{keyword} {arg}")
+            };
+
+            breakpoints.error = match text.parse() {
+                Ok(trigger) => match breakpoints.list.add(std::mem::take(&mut breakpoints.label), trigger) {
+                    Some(entry) => {
+                        sender.send(entry.set_msg()).unwrap();
+                        breakpoints.arg.clear();
+                        changed = true;
+                        None
+                    },
+                    None => Some("Already in the list".to_owned()),
+                },
+                Err(err) => Some(err),
+            };
         }
+    });
+
+    if let Some(ref error) = breakpoints.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+
+    if changed {
+        save_breakpoints(state);
     }
 }
 
-pub fn load_vram_texture(ctx: &Context, vram: &[u8]) -> TextureHandle {
-    ctx.load_texture(
-        "debug_vram",
-        ColorImage::from_rgb([128, 192], vram),
-        TextureOptions::NEAREST,
-    )
-}
\ No newline at end of file
+fn trace_menu(ui: &mut egui::Ui, state: &mut DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let trace_state = &mut state.trace;
+
+    egui::Grid::new("trace_options").num_columns(2).show(ui, |ui| {
+        ui.label("File");
+        ui.text_edit_singleline(&mut trace_state.path);
+        ui.end_row();
+
+        ui.label("Frames");
+        ui.add(egui::TextEdit::singleline(&mut trace_state.frames).hint_text("all, or e.g. 10-20"));
+        ui.end_row();
+
+        ui.label("PC");
+        ui.add(egui::TextEdit::singleline(&mut trace_state.pc).hint_text("all, or e.g. 4000-7FFF"));
+        ui.end_row();
+
+        ui.label("ROM bank");
+        ui.add(egui::TextEdit::singleline(&mut trace_state.bank).hint_text("all"));
+        ui.end_row();
+    });
+
+    ui.checkbox(&mut trace_state.pin_ly, "Hold LY at $90 (Gameboy Doctor)");
+
+    ui.horizontal(|ui| {
+        if ui.button("Start").clicked() {
+            match trace_filter(trace_state) {
+                Ok(filter) => {
+                    let path = PathBuf::from(trace_state.path.trim());
+                    sender.send(EmuMsgIn::TraceStart { path, filter, pin_ly: trace_state.pin_ly }).unwrap();
+                    trace_state.active = true;
+                    trace_state.error = None;
+                },
+                Err(err) => trace_state.error = Some(err),
+            }
+        }
+
+        if ui.add_enabled(trace_state.active, egui::Button::new("Stop")).clicked() {
+            sender.send(EmuMsgIn::TraceStop).unwrap();
+            trace_state.active = false;
+        }
+    });
+
+    if let Some(ref error) = trace_state.error {
+        ui.colored_label(ui.visuals().error_fg_color, error);
+    }
+}
+
+fn trace_filter(trace_state: &TraceState) -> Result<TraceFilter, String> {
+    Ok(TraceFilter {
+        frames: optional(&trace_state.frames).map(|text| trace::parse_frames(text).ok_or("Bad frame range")).transpose()?,
+        pc: optional(&trace_state.pc).map(|text| trace::parse_pcs(text).ok_or("Bad PC range")).transpose()?,
+        bank: optional(&trace_state.bank).map(|text| text.parse().map_err(|_| "Bad ROM bank")).transpose()?,
+    })
+}
+
+/// Blank fields mean no filter
+fn optional(text: &str) -> Option<&str> {
+    Some(text.trim()).filter(|text| !text.is_empty())
+}
+
+/// An address typed in by the user, as hex or a label
+pub fn parse_addr(state: &DebugState, text: &str) -> Option<u16> {
+    let text = text.trim();
+    u16::from_str_radix(text.trim_start_matches('$'), 16).ok().or_else(|| state.symbols.resolve(text))
+}
+
+/// The label at `addr`, taking the current ROM bank into account
+pub fn label(state: &DebugState, addr: u16) -> Option<&str> {
+    let bank = state.emu_state.as_ref().map(|s| s.rom_bank).unwrap_or(1);
+    state.symbols.label(addr, bank)
+}
+
+/// Loads `<rom>.sym` if there is one, clearing out any old symbols either way
+pub fn load_symbols(state: &mut DebugState, rom_path: &Path) {
+    let path = symbols::path(rom_path);
+
+    state.symbols = match std::fs::read_to_string(&path) {
+        Ok(text) => {
+            let (symbols, errors) = Symbols::parse(&text);
+
+            for error in errors {
+                eprintln!("Skipping symbol in {}: {error}", path.display());
+            }
+
+            symbols
+        },
+        Err(_) => Symbols::default(),
+    };
+}
+
+/// Adds a PC breakpoint at `addr`, or removes the one that's already there
+pub fn toggle_pc_breakpoint(state: &mut DebugState, addr: u16, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let list = &mut state.breakpoints.list;
+    let trigger = Trigger::Cpu(Breakpoint::Pc(addr));
+    let existing = list.entries.iter().find(|entry| entry.trigger == trigger).map(|entry| entry.id);
+
+    match existing {
+        Some(id) => {
+            if let Some(entry) = list.remove(id).filter(|entry| entry.enabled) {
+                sender.send(entry.unset_msg()).unwrap();
+            }
+        },
+        None => {
+            if let Some(entry) = list.add(String::new(), trigger) {
+                sender.send(entry.set_msg()).unwrap();
+            }
+        },
+    }
+
+    save_breakpoints(state);
+}
+
+pub fn load_breakpoints(state: &mut DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let Some(ref path) = state.breakpoints_path else {
+        return;
+    };
+
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return;
+    };
+
+    let (list, errors) = breakpoints::decode(&text);
+
+    for error in errors {
+        eprintln!("Skipping breakpoint in {}: {error}", path.display());
+    }
+
+    for msg in list.set_msgs() {
+        sender.send(msg).unwrap();
+    }
+
+    state.breakpoints.list = list;
+}
+
+fn save_breakpoints(state: &DebugState) {
+    let Some(ref path) = state.breakpoints_path else {
+        return;
+    };
+
+    // don't leave empty files lying around next to every rom
+    let res = if state.breakpoints.list.entries.is_empty() {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            res => res,
+        }
+    } else {
+        std::fs::write(path, breakpoints::encode(&state.breakpoints.list))
+    };
+
+    if let Err(err) = res {
+        eprintln!("Couldn't save breakpoints to {}: {err}", path.display());
+    }
+}
@@ -0,0 +1,126 @@
+use egui::{Color32, Context, RichText, Sense};
+use gamboye_core::{breakpoints::Trigger, disasm};
+use tokio::sync::mpsc;
+
+use crate::{comms::EmuMsgIn, gui::debug, runner::Breakpoint, state::DebugState};
+
+/// Lines kept above PC when following it, so there's some context
+const CONTEXT_LINES: usize = 4;
+
+pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let mut open = state.disasm.open;
+
+    egui::Window::new("Disassembly").open(&mut open).default_width(300.0).show(ctx, |ui| {
+        let Some(emu_state) = state.emu_state.as_ref() else {
+            ui.label("No rom loaded");
+            return;
+        };
+
+        let memory = &emu_state.memory;
+        let pc = emu_state.regs.pc;
+
+        let mut goto = false;
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut state.disasm.follow_pc, "Follow PC");
+
+            if ui.add_enabled(!state.disasm.history.is_empty(), egui::Button::new("Back")).clicked() {
+                state.disasm.scroll_to = state.disasm.history.pop();
+            }
+
+            let field = ui.add(egui::TextEdit::singleline(&mut state.disasm.goto).hint_text("addr or label").desired_width(96.0));
+            goto = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        });
+
+        if let Some(addr) = debug::parse_addr(state, &state.disasm.goto).filter(|_| goto) {
+            state.disasm.follow_pc = false;
+            state.disasm.scroll_to = Some(addr);
+        }
+
+        if state.disasm.follow_pc && state.disasm.last_pc != Some(pc) {
+            state.disasm.scroll_to = Some(pc);
+        }
+        state.disasm.last_pc = Some(pc);
+
+        if let Some(addr) = state.disasm.scroll_to {
+            state.disasm.anchor = addr;
+        }
+
+        if state.disasm.starts_anchor != Some(state.disasm.anchor) {
+            state.disasm.starts = disasm::line_starts(memory, &[pc, state.disasm.anchor]);
+            state.disasm.starts_anchor = Some(state.disasm.anchor);
+        }
+
+        let starts = &state.disasm.starts;
+
+        let text_style = egui::TextStyle::Monospace;
+        let row_height = ui.text_style_height(&text_style);
+        let mut scroll = egui::ScrollArea::vertical().auto_shrink(false);
+
+        if let Some(addr) = state.disasm.scroll_to.take() {
+            let row = starts.partition_point(|&start| start < addr).saturating_sub(CONTEXT_LINES);
+            scroll = scroll.vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+        }
+
+        let mut toggled = None;
+        let mut followed = None;
+
+        scroll.show_rows(ui, row_height, starts.len(), |ui, row_range| {
+            for &addr in &starts[row_range] {
+                let instruction = disasm::decode(memory, addr);
+                let bytes = (0..instruction.len as u16)
+                    .map(|i| format!("{:02X}", memory.get(addr.wrapping_add(i) as usize).copied().unwrap_or(0)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                // operands that have a label show it instead of the address
+                let text = match instruction.target.and_then(|target| debug::label(state, target).map(|label| (target, label))) {
+                    Some((target, label)) => instruction.text.replace(&format!("${target:04X}"), label),
+                    None => instruction.text.clone(),
+                };
+
+                ui.horizontal(|ui| {
+                    let gutter = match breakpoint_at(state, addr) {
+                        Some(true) => RichText::new("●").color(Color32::RED),
+                        Some(false) => RichText::new("○").color(Color32::RED),
+                        None => RichText::new("  "),
+                    };
+
+                    if ui.add(egui::Label::new(gutter.monospace()).sense(Sense::click())).on_hover_text("Toggle breakpoint").clicked() {
+                        toggled = Some(addr);
+                    }
+
+                    if let Some(label) = debug::label(state, addr) {
+                        ui.label(RichText::new(format!("{label}:")).monospace().color(Color32::LIGHT_BLUE));
+                    }
+
+                    let line = RichText::new(format!("{addr:04X}  {bytes:<8}  {text}")).monospace();
+                    let response = ui.selectable_label(addr == pc, line);
+
+                    if response.double_clicked() {
+                        followed = instruction.target.map(|target| (addr, target));
+                    }
+                });
+            }
+        });
+
+        if let Some(addr) = toggled {
+            debug::toggle_pc_breakpoint(state, addr, sender);
+        }
+
+        if let Some((from, target)) = followed {
+            state.disasm.follow_pc = false;
+            state.disasm.history.push(from);
+            state.disasm.scroll_to = Some(target);
+        }
+    });
+
+    state.disasm.open = open;
+}
+
+/// Whether there's a PC breakpoint at `addr`, and if so whether it's enabled
+fn breakpoint_at(state: &DebugState, addr: u16) -> Option<bool> {
+    state.breakpoints.list.entries.iter()
+        .find(|entry| entry.trigger == Trigger::Cpu(Breakpoint::Pc(addr)))
+        .map(|entry| entry.enabled)
+}
@@ -4,7 +4,12 @@ use egui::{vec2, ColorImage, Context, InnerResponse, TextureOptions};
 
 use crate::{comms, runner::{HEIGHT, WIDTH}};
 
-use super::TopState;
+use super::{oam, TopState};
+
+const REWIND_KEY: egui::Key = egui::Key::Backspace;
+/// Not Tab, which egui uses to move focus between widgets
+const TURBO_KEY: egui::Key = egui::Key::T;
+const FRAME_ADVANCE_KEY: egui::Key = egui::Key::F;
 
 pub fn show(ctx: &Context, state: &mut TopState) -> InnerResponse<()> {
     const BUTTONS: [Keybind; 8] = [
@@ -30,6 +35,28 @@ pub fn show(ctx: &Context, state: &mut TopState) -> InnerResponse<()> {
         }
     };
 
+    // hotkeys shouldn't fire while something's being typed, but keys let go of still count
+    let typing = ctx.wants_keyboard_input();
+
+    if let Some(ref sender) = state.emu.sender {
+        if !typing && ctx.input(|i| i.key_pressed(REWIND_KEY)) {
+            sender.send(comms::EmuMsgIn::RewindStart).unwrap();
+        } else if ctx.input(|i| i.key_released(REWIND_KEY)) {
+            sender.send(comms::EmuMsgIn::RewindStop).unwrap();
+        }
+
+        if !typing && ctx.input(|i| i.key_pressed(FRAME_ADVANCE_KEY)) {
+            sender.send(comms::EmuMsgIn::FrameAdvance).unwrap();
+            state.debug.stopped = true;
+        }
+
+        if !typing && ctx.input(|i| i.key_pressed(TURBO_KEY)) {
+            sender.send(comms::EmuMsgIn::Turbo(true)).unwrap();
+        } else if ctx.input(|i| i.key_released(TURBO_KEY)) {
+            sender.send(comms::EmuMsgIn::Turbo(false)).unwrap();
+        }
+    }
+
     egui::CentralPanel::default().show(ctx, |ui| {
         if state.emu.atoms.fb_pending.load(Ordering::Relaxed) {
             state.emu.atoms.fb_pending.store(false, Ordering::Relaxed);
@@ -43,13 +70,13 @@ pub fn show(ctx: &Context, state: &mut TopState) -> InnerResponse<()> {
             state.emu.texture = ctx.load_texture("emu_display", new_display, TextureOptions::NEAREST);
 
             crate::gui::perf::record_frame(state);
-            crate::gui::perf::ratelimit(state);
         }
 
         state.debug.emu_status = *state.emu.atoms.status.lock();
 
         ui.vertical_centered(|ui| {
-            ui.add(egui::Image::from_texture(egui::load::SizedTexture::from_handle(&state.emu.texture)).maintain_aspect_ratio(true).fit_to_fraction(vec2(1.0, 1.0)));
+            let display = ui.add(egui::Image::from_texture(egui::load::SizedTexture::from_handle(&state.emu.texture)).maintain_aspect_ratio(true).fit_to_fraction(vec2(1.0, 1.0)));
+            oam::highlight(ui.painter(), display.rect, &state.debug);
         });
     })
 }
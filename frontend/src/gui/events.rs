@@ -0,0 +1,142 @@
+use egui::{pos2, vec2, Color32, Context, Rect, RichText, Sense, Stroke};
+use gamboye_core::events::{self, Category, Event};
+use tokio::sync::mpsc;
+
+use crate::{comms::EmuMsgIn, gui::debug, runner::HEIGHT, state::{DebugState, EventsState}};
+
+/// Frames of events kept around to look back through
+const KEEP_FRAMES: usize = 60;
+/// Screen pixels per scanline in the grid, which is 1 pixel per dot across
+const LINE_HEIGHT: f32 = 2.0;
+/// How close the mouse has to be to an event in the grid to show it
+const HOVER_RADIUS: f32 = 4.0;
+
+pub fn show(ctx: &Context, state: &mut DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let mut open = state.events.open;
+
+    egui::Window::new("Events").open(&mut open).default_width(480.0).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut state.events.logging, "Log events").changed() {
+                sender.send(EmuMsgIn::LogEvents(state.events.logging)).unwrap();
+            }
+
+            if ui.button("Clear").clicked() {
+                state.events.events.clear();
+            }
+
+            let latest = state.events.events.last().map_or(0, |event| event.frame);
+            let mut follow = state.events.frame.is_none();
+            ui.checkbox(&mut follow, "Latest frame");
+
+            let mut frame = state.events.frame.unwrap_or(latest);
+            let first = state.events.events.first().map_or(0, |event| event.frame);
+            ui.add_enabled(!follow, egui::DragValue::new(&mut frame).clamp_range(first..=latest).prefix("Frame "));
+            state.events.frame = (!follow).then_some(frame);
+        });
+
+        ui.horizontal_wrapped(|ui| {
+            for category in Category::ALL {
+                let mut shown = !state.events.hidden.contains(&category);
+
+                if ui.checkbox(&mut shown, RichText::new(category.name()).color(color(category))).changed() {
+                    match shown {
+                        true => state.events.hidden.retain(|&hidden| hidden != category),
+                        false => state.events.hidden.push(category),
+                    }
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.add(egui::TextEdit::singleline(&mut state.events.filter).hint_text("text in the event, like FF40").desired_width(160.0));
+        });
+
+        if !state.events.logging && state.events.events.is_empty() {
+            ui.weak("Turn on logging to record events");
+            return;
+        }
+
+        let frame = state.events.frame.unwrap_or_else(|| state.events.events.last().map_or(0, |event| event.frame));
+        let filter = state.events.filter.trim().to_ascii_uppercase();
+        let shown = state.events.events.iter()
+            .filter(|event| event.frame == frame && !state.events.hidden.contains(&event.kind.category()))
+            .filter(|event| filter.is_empty() || event.kind.to_string().to_ascii_uppercase().contains(&filter))
+            .collect::<Vec<_>>();
+
+        grid(ui, &shown);
+        ui.separator();
+
+        ui.label(format!("{} events in frame {frame}", shown.len()));
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical().id_source("event_list").max_height(240.0).show_rows(ui, row_height, shown.len(), |ui, row_range| {
+            for event in &shown[row_range] {
+                let label = debug::label(state, event.pc).map(|label| format!(" {label}")).unwrap_or_default();
+
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("LY {:3} dot {:3}  PC ${:04X}{label}", event.ly, event.dot, event.pc));
+                    ui.label(RichText::new(event.kind.to_string()).monospace().color(color(event.kind.category())));
+                });
+            }
+        });
+    });
+
+    state.events.open = open;
+}
+
+/// Adds a batch from the runner, dropping frames that have fallen out of the window
+pub fn record(state: &mut EventsState, batch: Vec<Event>) {
+    state.events.extend(batch);
+
+    let latest = state.events.last().map_or(0, |event| event.frame);
+    let oldest = latest.saturating_sub(KEEP_FRAMES - 1);
+    state.events.retain(|event| event.frame >= oldest);
+}
+
+/// A frame laid out like the PPU sees it, dots across and scanlines down, with a mark per event
+fn grid(ui: &mut egui::Ui, events: &[&Event]) {
+    let size = vec2(events::DOTS as f32, events::LINES as f32 * LINE_HEIGHT);
+    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+    let rect = response.rect;
+    let pos = |event: &Event| rect.min + vec2(event.dot as f32, event.ly as f32 * LINE_HEIGHT);
+
+    painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+
+    // VBlank, after the last visible line
+    let vblank = Rect::from_min_max(pos2(rect.left(), rect.top() + HEIGHT as f32 * LINE_HEIGHT), rect.max);
+    painter.rect_filled(vblank, 0.0, Color32::from_gray(40));
+
+    for event in events {
+        painter.circle_filled(pos(event), 1.5, color(event.kind.category()));
+    }
+
+    let Some(hover) = response.hover_pos() else {
+        return;
+    };
+
+    let ly = ((hover.y - rect.top()) / LINE_HEIGHT) as u8;
+    let dot = (hover.x - rect.left()) as u16;
+    painter.hline(rect.x_range(), hover.y, Stroke::new(1.0, Color32::from_gray(80)));
+
+    let near = events.iter()
+        .filter(|event| pos(event).distance(hover) <= HOVER_RADIUS)
+        .map(|event| format!("LY {} dot {} PC ${:04X}: {}", event.ly, event.dot, event.pc, event.kind))
+        .collect::<Vec<_>>();
+
+    response.on_hover_text(match near.is_empty() {
+        true => format!("LY {ly} dot {dot}"),
+        false => near.join("\n"),
+    });
+}
+
+fn color(category: Category) -> Color32 {
+    match category {
+        Category::Interrupt => Color32::from_rgb(0xFF, 0x50, 0x50),
+        Category::Ppu => Color32::from_rgb(0x50, 0xC0, 0xFF),
+        Category::Apu => Color32::from_rgb(0x80, 0xFF, 0x80),
+        Category::Timer => Color32::from_rgb(0xFF, 0xD0, 0x40),
+        Category::Dma => Color32::from_rgb(0xD0, 0x80, 0xFF),
+        Category::Halt => Color32::from_gray(0xC0),
+    }
+}
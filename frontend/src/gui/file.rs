@@ -0,0 +1,158 @@
+use std::path::PathBuf;
+
+use egui::{Color32, Context, Key, Modifiers, RichText};
+
+use gamboye_core::movie::{self, MovieMode, MovieStart};
+
+use crate::{comms::EmuMsgIn, savestate};
+
+use super::TopState;
+
+/// What save states leave out, see the savestate module
+const STATE_LIMITS: &str = "States keep memory, registers and cartridge RAM, but not where the PPU, APU or timer were. Loading one restarts the frame from line 0.";
+
+const SLOT_KEYS: [Key; savestate::SLOTS] = [
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5,
+    Key::F6, Key::F7, Key::F8, Key::F9, Key::F10,
+];
+
+pub fn menu(ui: &mut egui::Ui, state: &mut TopState) {
+    if ui.button("Open...").clicked() {
+        ui.close_menu();
+
+        let picked = rfd::FileDialog::new()
+            .add_filter("Game Boy ROM", &["gb", "gbc"])
+            .add_filter("All files", &["*"])
+            .pick_file();
+
+        if let Some(path) = picked {
+            open_rom(ui.ctx(), state, path);
+        }
+    }
+
+    if let Some(ref error) = state.file.error {
+        ui.colored_label(Color32::RED, error);
+    }
+
+    let (Some(ref sender), Some(ref rom_path)) = (&state.emu.sender, &state.emu.rom_path) else {
+        return;
+    };
+
+    ui.separator();
+
+    ui.menu_button("Save State", |ui| {
+        ui.label(RichText::new(STATE_LIMITS).weak().small());
+        ui.separator();
+
+        for slot in 1..=savestate::SLOTS {
+            if ui.button(format!("Slot {slot} (Shift+F{slot})")).clicked() {
+                sender.send(EmuMsgIn::SaveState(slot)).unwrap();
+                ui.close_menu();
+            }
+        }
+    });
+
+    ui.menu_button("Load State", |ui| {
+        ui.label(RichText::new(STATE_LIMITS).weak().small());
+        ui.separator();
+
+        for slot in 1..=savestate::SLOTS {
+            let exists = savestate::path(rom_path, slot).exists();
+
+            if ui.add_enabled(exists, egui::Button::new(format!("Slot {slot} (F{slot})"))).clicked() {
+                sender.send(EmuMsgIn::LoadState(slot)).unwrap();
+                ui.close_menu();
+            }
+        }
+    });
+
+    ui.menu_button("Movie", |ui| {
+        match state.file.movie {
+            Some(status) => {
+                let mode = match status.mode {
+                    MovieMode::Recording => "Recording",
+                    MovieMode::Playing if status.finished => "Finished",
+                    MovieMode::Playing => "Playing",
+                };
+                ui.label(format!("{mode}, {} inputs, {} rerecords", status.events, status.rerecords));
+
+                let mut read_only = status.read_only;
+                if ui.checkbox(&mut read_only, "Read-only").changed() {
+                    sender.send(EmuMsgIn::MovieReadOnly(read_only)).unwrap();
+                }
+
+                if ui.button("Stop").clicked() {
+                    sender.send(EmuMsgIn::MovieStop).unwrap();
+                    ui.close_menu();
+                }
+            },
+            None => {
+                if ui.button("Record from power-on").clicked() {
+                    sender.send(EmuMsgIn::MovieRecord(MovieStart::PowerOn)).unwrap();
+                    ui.close_menu();
+                }
+
+                if ui.button("Record from now").clicked() {
+                    sender.send(EmuMsgIn::MovieRecord(MovieStart::Now)).unwrap();
+                    ui.close_menu();
+                }
+
+                let exists = movie::path(rom_path).exists();
+                if ui.add_enabled(exists, egui::Button::new("Play")).clicked() {
+                    sender.send(EmuMsgIn::MoviePlay).unwrap();
+                    ui.close_menu();
+                }
+            }
+        }
+    });
+}
+
+/// F1-F10 load a slot, Shift+F1-F10 save to it
+pub fn handle_shortcuts(ctx: &Context, state: &mut TopState) {
+    let Some(ref sender) = state.emu.sender else {
+        return;
+    };
+
+    for (i, key) in SLOT_KEYS.into_iter().enumerate() {
+        let slot = i + 1;
+
+        if ctx.input_mut(|i| i.consume_key(Modifiers::SHIFT, key)) {
+            sender.send(EmuMsgIn::SaveState(slot)).unwrap();
+        } else if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, key)) {
+            sender.send(EmuMsgIn::LoadState(slot)).unwrap();
+        }
+    }
+}
+
+/// Loads the first ROM dropped onto the window, if there is one
+pub fn handle_dropped_files(ctx: &Context, state: &mut TopState) {
+    let Some(file) = ctx.input(|i| i.raw.dropped_files.first().cloned()) else {
+        return;
+    };
+
+    match (file.path, file.bytes) {
+        (Some(path), Some(bytes)) => {
+            state.file.error = None;
+            state.load_rom(ctx, Some(path), &bytes);
+        },
+        (Some(path), None) => open_rom(ctx, state, path),
+        // some platforms only hand over the bytes, which leaves nowhere next to the rom to keep saves
+        (None, Some(bytes)) => {
+            state.file.error = None;
+            state.load_rom(ctx, None, &bytes);
+        },
+        (None, None) => {},
+    }
+}
+
+fn open_rom(ctx: &Context, state: &mut TopState, path: PathBuf) {
+    match std::fs::read(&path) {
+        Ok(rom) => {
+            state.file.error = None;
+            state.load_rom(ctx, Some(path), &rom);
+        },
+        Err(err) => {
+            state.file.error = Some(format!("Couldn't open {}: {err}", path.display()));
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use egui::{Color32, Context, RichText};
+use gamboye_core::io::{self, Group, Register};
+
+use crate::state::{DebugState, IoState, StateDump};
+
+const CHANGED: Color32 = Color32::YELLOW;
+
+pub fn show(ctx: &Context, state: &mut DebugState) {
+    let mut open = state.io.open;
+
+    egui::Window::new("IO Registers").open(&mut open).default_height(500.0).show(ctx, |ui| {
+        let Some(emu_state) = state.emu_state.as_ref() else {
+            ui.label("No rom loaded");
+            return;
+        };
+
+        let current = io::snapshot(&emu_state.memory);
+        let prev = &state.io.prev;
+
+        ui.checkbox(&mut state.io.decode, "Show bitfields");
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for group in Group::ALL {
+                egui::CollapsingHeader::new(group.name()).default_open(true).show(ui, |ui| {
+                    egui::Grid::new(("io_registers", group.name())).striped(true).show(ui, |ui| {
+                        for reg in io::group(group) {
+                            register_row(ui, reg, &current, prev, state.io.decode);
+                        }
+                    });
+
+                    if group == Group::Sound {
+                        wave_ram(ui, &current, prev);
+                    }
+                });
+            }
+        });
+    });
+
+    state.io.open = open;
+}
+
+/// Keeps the registers from before `new` around for highlighting. Dumps that don't change
+/// anything, like the runner repeating itself while paused, leave the last step's changes up.
+pub fn record(state: &mut IoState, old: &StateDump, new: &StateDump) {
+    let (old_io, new_io) = (io::snapshot(&old.memory), io::snapshot(&new.memory));
+
+    if old.regs.pc != new.regs.pc || old_io != new_io {
+        state.prev = old_io;
+    }
+}
+
+fn register_row(ui: &mut egui::Ui, reg: &Register, current: &[u8], prev: &[u8], decode: bool) {
+    let index = io::snapshot_index(reg.addr);
+    let value = current.get(index).copied().unwrap_or(0);
+    let old = prev.get(index).copied().unwrap_or(value);
+    let text_color = ui.visuals().text_color();
+    let color = |changed: bool| if changed { CHANGED } else { text_color };
+
+    ui.monospace(format!("${:04X}", reg.addr));
+    ui.label(RichText::new(reg.name).monospace().strong());
+    ui.label(RichText::new(format!("${value:02X} {value:08b}")).monospace().color(color(value != old)))
+        .on_hover_text(format!("Was ${old:02X}"));
+
+    if decode && !reg.fields.is_empty() {
+        ui.vertical(|ui| {
+            for field in reg.fields {
+                let changed = field.get(value) != field.get(old);
+                ui.label(RichText::new(format!("{:>3}  {}", field.bits(), field.describe(value))).monospace().color(color(changed)));
+            }
+        });
+    } else {
+        ui.monospace(value.to_string());
+    }
+
+    ui.end_row();
+}
+
+fn wave_ram(ui: &mut egui::Ui, current: &[u8], prev: &[u8]) {
+    let (start, end) = io::WAVE_RAM;
+
+    ui.horizontal(|ui| {
+        ui.monospace(format!("${start:04X} Wave"));
+
+        for addr in start..=end {
+            let index = io::snapshot_index(addr);
+            let value = current.get(index).copied().unwrap_or(0);
+            let changed = prev.get(index).is_some_and(|&old| old != value);
+            let text = RichText::new(format!("{value:02X}")).monospace();
+
+            ui.label(if changed { text.color(CHANGED) } else { text });
+        }
+    });
+}
@@ -0,0 +1,154 @@
+use egui::{load::SizedTexture, pos2, vec2, Color32, ColorImage, Context, Painter, Pos2, Rect, Sense, Stroke, TextureOptions};
+use gamboye_core::vram::{self, MapEntry};
+
+use crate::{runner::{HEIGHT, WIDTH}, state::DebugState};
+
+const LCDC: usize = 0xFF40;
+const WY: usize = 0xFF4A;
+const WX: usize = 0xFF4B;
+
+pub fn show(ctx: &Context, state: &mut DebugState) {
+    let mut open = state.maps.open;
+
+    egui::Window::new("Tile Maps").open(&mut open).resizable(false).show(ctx, |ui| {
+        let Some(emu_state) = state.emu_state.as_ref() else {
+            ui.label("No rom loaded");
+            return;
+        };
+
+        let maps = &mut state.maps;
+        let io = |addr: usize| emu_state.memory.get(addr).copied().unwrap_or(0);
+
+        let lcdc = io(LCDC);
+        let unsigned = lcdc & 0x10 != 0;
+        let bg_map = if lcdc & 0x08 != 0 { vram::MAP_1 } else { vram::MAP_0 };
+        let window_map = if lcdc & 0x40 != 0 { vram::MAP_1 } else { vram::MAP_0 };
+        let (scx, scy) = (emu_state.io_regs.scx as f32, emu_state.io_regs.scy as f32);
+        // WX is offset by 7 so the window can start partly off screen
+        let (wx, wy) = (io(WX) as f32 - 7.0, io(WY) as f32);
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut maps.window, false, "BG");
+            ui.radio_value(&mut maps.window, true, "Window");
+            ui.separator();
+            ui.checkbox(&mut maps.grid, "Grid");
+            ui.checkbox(&mut maps.viewport, "Viewport");
+        });
+
+        let map = if maps.window { window_map } else { bg_map };
+        ui.label(format!(
+            "Map ${:04X}, tiles at {}, SCX {scx} SCY {scy}, WX {} WY {wy}",
+            0x8000 + map,
+            if unsigned { "$8000" } else { "$8800" },
+            io(WX),
+        ));
+
+        let banks = (0..2).map_while(|bank| emu_state.vram(bank)).collect::<Vec<_>>();
+        if banks.is_empty() {
+            ui.label("VRAM isn't available");
+            return;
+        }
+
+        let palettes = (0..if emu_state.is_cgb() { 8 } else { 1 })
+            .map(|index| super::tiles::palette(emu_state, false, index))
+            .collect::<Vec<_>>();
+
+        let image = vram::tile_map(&banks, map, unsigned, &palettes);
+        let texture = ctx.load_texture("tile_map_viewer", ColorImage::from_rgb([256, 256], &image), TextureOptions::NEAREST);
+
+        let zoom = 2.0;
+        let response = ui.add(egui::Image::from_texture(SizedTexture::new(texture.id(), vec2(256.0, 256.0) * zoom)).sense(Sense::hover()));
+        let rect = response.rect;
+        let painter = ui.painter_at(rect);
+        let to_screen = |x: f32, y: f32| rect.min + vec2(x, y) * zoom;
+
+        if maps.grid {
+            let stroke = Stroke::new(1.0, Color32::from_gray(128).gamma_multiply(0.5));
+
+            for i in 1..32 {
+                let offset = i as f32 * 8.0 * zoom;
+                painter.vline(rect.left() + offset, rect.y_range(), stroke);
+                painter.hline(rect.x_range(), rect.top() + offset, stroke);
+            }
+        }
+
+        if maps.viewport {
+            let stroke = Stroke::new(2.0, Color32::RED);
+
+            if maps.window {
+                // the window always draws from its top left, so only the part that fits on screen shows
+                let size = vec2((WIDTH as f32 - wx).clamp(0.0, WIDTH as f32), (HEIGHT as f32 - wy).clamp(0.0, HEIGHT as f32));
+                painter.rect_stroke(Rect::from_min_size(rect.min, size * zoom), 0.0, stroke);
+            } else {
+                wrapped_rect(&painter, to_screen, (scx, scy), stroke);
+
+                // where the window's top left lands in the BG map, if it's on
+                if lcdc & 0x20 != 0 {
+                    let origin = to_screen((scx + wx).rem_euclid(256.0), (scy + wy).rem_euclid(256.0));
+                    marker(&painter, origin, Color32::LIGHT_BLUE);
+                }
+            }
+        }
+
+        if let Some(pos) = response.hover_pos() {
+            let x = ((pos.x - rect.left()) / (8.0 * zoom)) as usize;
+            let y = ((pos.y - rect.top()) / (8.0 * zoom)) as usize;
+
+            if x < 32 && y < 32 {
+                let entry = vram::map_entry(&banks, map, (x, y), unsigned);
+                let cell = Rect::from_min_size(to_screen(x as f32 * 8.0, y as f32 * 8.0), vec2(8.0, 8.0) * zoom);
+                painter.rect_stroke(cell, 0.0, Stroke::new(1.0, Color32::YELLOW));
+
+                response.on_hover_text(hover_text(&entry, (x, y), banks.len() > 1));
+            }
+        }
+
+        maps.texture = Some(texture);
+    });
+
+    state.maps.open = open;
+}
+
+/// The 160x144 screen starting at `(x, y)` in the map, split up where it wraps past 256
+fn wrapped_rect(painter: &Painter, to_screen: impl Fn(f32, f32) -> Pos2, (x, y): (f32, f32), stroke: Stroke) {
+    for dx in [0.0, -256.0] {
+        for dy in [0.0, -256.0] {
+            let min = to_screen(x + dx, y + dy);
+            let max = to_screen(x + dx + WIDTH as f32, y + dy + HEIGHT as f32);
+
+            // the painter clips anything outside the map
+            painter.rect_stroke(Rect::from_min_max(min, max), 0.0, stroke);
+        }
+    }
+}
+
+fn marker(painter: &Painter, pos: Pos2, color: Color32) {
+    let stroke = Stroke::new(2.0, color);
+    painter.line_segment([pos2(pos.x - 6.0, pos.y), pos2(pos.x + 6.0, pos.y)], stroke);
+    painter.line_segment([pos2(pos.x, pos.y - 6.0), pos2(pos.x, pos.y + 6.0)], stroke);
+}
+
+fn hover_text(entry: &MapEntry, (x, y): (usize, usize), cgb: bool) -> String {
+    let mut text = format!(
+        "Cell {x}, {y} at ${:04X}\nTile {} (${:03X}) at ${:04X}",
+        entry.addr,
+        entry.tile,
+        entry.tile,
+        vram::tile_addr(entry.tile),
+    );
+
+    if cgb {
+        let (x_flip, y_flip) = entry.flip();
+        text += &format!(
+            "\nAttributes ${:02X}: palette {}, bank {}{}{}{}",
+            entry.attrs,
+            entry.palette(),
+            entry.bank(),
+            if x_flip { ", X flip" } else { "" },
+            if y_flip { ", Y flip" } else { "" },
+            if entry.priority() { ", over OBJ" } else { "" },
+        );
+    }
+
+    text
+}
@@ -0,0 +1,140 @@
+use egui::{load::SizedTexture, pos2, vec2, Color32, ColorImage, Context, Painter, Rect, RichText, Sense, Stroke, TextureOptions};
+use gamboye_core::{oam::{self, Sprite}, vram};
+
+use crate::{gui::tiles, runner::{HEIGHT, WIDTH}, state::{DebugState, StateDump}};
+
+const LCDC: usize = 0xFF40;
+/// Screen pixels per sprite pixel in the previews
+const PREVIEW_ZOOM: f32 = 2.0;
+
+pub fn show(ctx: &Context, state: &mut DebugState) {
+    let mut open = state.oam.open;
+    state.oam.hovered = None;
+
+    egui::Window::new("OAM").open(&mut open).default_height(400.0).show(ctx, |ui| {
+        let Some(emu_state) = state.emu_state.as_ref() else {
+            ui.label("No rom loaded");
+            return;
+        };
+
+        let tall = emu_state.memory.get(LCDC).is_some_and(|lcdc| lcdc & 0x04 != 0);
+        let sprites = oam::sprites(&emu_state.memory);
+        let dropped = oam::dropped_lines(&sprites, tall);
+        let height = if tall { 16 } else { 8 };
+
+        // every sprite's preview side by side, 8 pixels apart
+        let texture = ctx.load_texture("oam_viewer", ColorImage::from_rgb([oam::SPRITES * 8, 16], &previews(emu_state, &sprites, tall)), TextureOptions::NEAREST);
+
+        ui.label(format!("{} sprites, {} dropped by the {} per line limit", if tall { "8x16" } else { "8x8" }, dropped.iter().filter(|&&lines| lines > 0).count(), oam::PER_LINE));
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("oam_sprites").striped(true).show(ui, |ui| {
+                for heading in ["#", "", "X", "Y", "Tile", "Flags", ""] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+
+                for (sprite, &dropped) in sprites.iter().zip(&dropped) {
+                    let mut hovered = ui.monospace(format!("{:02}", sprite.index)).hovered();
+
+                    let uv = Rect::from_min_size(pos2(sprite.index as f32 / oam::SPRITES as f32, 0.0), vec2(1.0 / oam::SPRITES as f32, height as f32 / 16.0));
+                    let preview = egui::Image::from_texture(SizedTexture::new(texture.id(), vec2(8.0, height as f32) * PREVIEW_ZOOM)).uv(uv);
+                    hovered |= ui.add(preview.sense(Sense::hover())).hovered();
+
+                    let (x, y) = sprite.screen_pos();
+                    hovered |= ui.monospace(format!("{x:4}")).hovered();
+                    hovered |= ui.monospace(format!("{y:4}")).hovered();
+                    hovered |= ui.monospace(format!("${:02X}", sprite.tile)).hovered();
+                    hovered |= ui.monospace(flags_text(sprite, emu_state.is_cgb())).hovered();
+
+                    if dropped > 0 {
+                        ui.label(RichText::new(format!("dropped on {dropped} lines")).color(Color32::YELLOW))
+                            .on_hover_text(format!("More than {} sprites share these lines, and this one comes later in OAM", oam::PER_LINE));
+                    } else if !on_screen(sprite, tall) {
+                        ui.weak("off screen");
+                    } else {
+                        ui.label("");
+                    }
+
+                    ui.end_row();
+
+                    if hovered {
+                        state.oam.hovered = Some(sprite.index);
+                    }
+                }
+            });
+        });
+
+        state.oam.texture = Some(texture);
+    });
+
+    state.oam.open = open;
+}
+
+/// Outlines the hovered sprite on the main display, where `display` is the rect the screen was drawn in
+pub fn highlight(painter: &Painter, display: Rect, state: &DebugState) {
+    let (Some(index), Some(emu_state)) = (state.oam.hovered, state.emu_state.as_ref()) else {
+        return;
+    };
+
+    let tall = emu_state.memory.get(LCDC).is_some_and(|lcdc| lcdc & 0x04 != 0);
+    let Some(sprite) = oam::sprites(&emu_state.memory).into_iter().nth(index) else {
+        return;
+    };
+
+    let scale = display.width() / WIDTH as f32;
+    let (x, y) = sprite.screen_pos();
+    let rect = Rect::from_min_size(
+        display.min + vec2(x as f32, y as f32) * scale,
+        vec2(8.0, if tall { 16.0 } else { 8.0 }) * scale,
+    );
+
+    painter.with_clip_rect(display).rect_stroke(rect, 0.0, Stroke::new(2.0, Color32::RED));
+}
+
+fn on_screen(sprite: &Sprite, tall: bool) -> bool {
+    let (x, _) = sprite.screen_pos();
+    let lines = sprite.lines(tall);
+
+    x > -8 && x < WIDTH as i16 && lines.end > 0 && lines.start < HEIGHT as i16
+}
+
+fn flags_text(sprite: &Sprite, cgb: bool) -> String {
+    let (x_flip, y_flip) = sprite.flip();
+    let palette = match cgb {
+        true => format!("pal {} bank {}", sprite.cgb_palette(), sprite.bank()),
+        false => format!("OBP{}", sprite.dmg_palette()),
+    };
+
+    format!(
+        "${:02X} {palette}{}{}{}",
+        sprite.flags,
+        if x_flip { " xflip" } else { "" },
+        if y_flip { " yflip" } else { "" },
+        if sprite.behind_bg() { " behind" } else { "" },
+    )
+}
+
+/// Each sprite drawn with its own palette and flips into an 8x16 slot, one after another
+fn previews(emu_state: &StateDump, sprites: &[Sprite], tall: bool) -> Vec<u8> {
+    let width = oam::SPRITES * 8;
+    let mut image = vec![0; width * 16 * 3];
+
+    for sprite in sprites {
+        let (palette, bank) = match emu_state.is_cgb() {
+            true => (tiles::palette(emu_state, true, sprite.cgb_palette()), sprite.bank()),
+            false => (tiles::palette(emu_state, true, sprite.dmg_palette()), 0),
+        };
+
+        let Some(vram) = emu_state.vram(bank).or_else(|| emu_state.vram(0)) else {
+            continue;
+        };
+
+        for (i, tile) in sprite.tiles(tall).into_iter().enumerate() {
+            vram::draw_tile(&mut image, width, (sprite.index * 8, i * 8), vram, tile, &palette, sprite.flip());
+        }
+    }
+
+    image
+}
@@ -1,8 +1,8 @@
-use std::{io::{stdout, Write}, time::{Duration, Instant}};
+use std::{io::{stdout, Write}, time::Instant};
 
 use egui::Context;
 
-use crate::{comms::EmuMsgIn, state::PerfState};
+use crate::state::PerfState;
 
 pub const MAX_FPS_HISTORY: usize = 10;
 
@@ -63,29 +63,3 @@ pub fn record_frame(state: &mut super::TopState) {
         state.perf.frames = 0;
     }
 }
-
-pub fn ratelimit(state: &mut super::TopState) {
-    let now = Instant::now();
-    let duration = now.duration_since(state.perf.last_frame);
-    const MIN_FRAMETIME: u64 = 1_000_000 / crate::gui::MAX_FRAMERATE as u64;
-
-    // too fast >:(
-    if duration.as_micros() < MIN_FRAMETIME as u128 {
-        if let Some(ref emu_channel) = state.emu.sender {
-            let awaken = state.perf.last_frame + Duration::from_micros(MIN_FRAMETIME);
-            state.perf.last_frame = awaken;
-
-            emu_channel.send(EmuMsgIn::FrameLimit).unwrap();
-
-            let awaken_sender = emu_channel.clone();
-            tokio::spawn(async move {
-                while Instant::now() < awaken {
-                    tokio::time::sleep_until((Instant::now() + Duration::from_micros(100)).into()).await;
-                }
-                awaken_sender.send(EmuMsgIn::FrameUnlimit).unwrap();
-            });
-        }
-    } else {
-        state.perf.last_frame = now;
-    }
-}
\ No newline at end of file
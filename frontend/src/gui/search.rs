@@ -0,0 +1,174 @@
+use egui::{Context, RichText};
+use gamboye_core::search::{self, Filter, Search, Watch, Width};
+use tokio::sync::mpsc;
+
+use crate::{comms::EmuMsgIn, gui::debug, state::{DebugState, SearchState}};
+
+const FILTERS: [(Filter, &str); 5] = [
+    (Filter::Equal, "Equal"),
+    (Filter::Changed, "Changed"),
+    (Filter::Unchanged, "Unchanged"),
+    (Filter::Increased, "Increased"),
+    (Filter::Decreased, "Decreased"),
+];
+
+pub fn show(ctx: &Context, state: &mut SearchState, debug: &DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    let mut open = state.open;
+
+    egui::Window::new("Memory Search").open(&mut open).default_width(320.0).show(ctx, |ui| {
+        let Some(memory) = debug.emu_state.as_ref().map(|s| &s.memory) else {
+            ui.label("No rom loaded");
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(state.search.is_none(), |ui| {
+                ui.radio_value(&mut state.width, Width::Byte, "8 bit");
+                ui.radio_value(&mut state.width, Width::Word, "16 bit");
+            });
+
+            if ui.button("New Search").on_hover_text("Snapshot RAM and start over").clicked() {
+                state.search = Some(Search::new(memory, state.width));
+            }
+
+            if ui.add_enabled(state.search.is_some(), egui::Button::new("Clear")).clicked() {
+                state.search = None;
+            }
+        });
+
+        let Some(ref mut current) = state.search else {
+            ui.label("Start a search to snapshot RAM");
+            watch_list(ui, state, debug, sender);
+            return;
+        };
+
+        ui.horizontal_wrapped(|ui| {
+            for (filter, name) in FILTERS {
+                if ui.button(name).clicked() {
+                    current.filter(memory, filter);
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut state.value).hint_text("42 or $2A").desired_width(64.0));
+
+            let value = parse_value(&state.value).filter(|&value| value <= current.width.max());
+            if ui.add_enabled(value.is_some(), egui::Button::new("Value")).clicked() {
+                current.filter(memory, Filter::Value(value.unwrap()));
+            }
+        });
+
+        let width = current.width;
+        let candidates = current.candidates();
+        ui.label(format!("{} candidates", candidates.len()));
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        let mut watched = None;
+
+        egui::ScrollArea::vertical().id_source("search_candidates").max_height(200.0).show_rows(ui, row_height, candidates.len(), |ui, row_range| {
+            for &addr in &candidates[row_range] {
+                ui.horizontal(|ui| {
+                    let name = debug::label(debug, addr).map(|label| format!(" {label}")).unwrap_or_default();
+                    let now = search::read(memory, addr, width);
+
+                    ui.monospace(format!("${addr:04X}{name}"));
+                    ui.monospace(format!("{} -> {now}", current.previous(addr)));
+
+                    if ui.small_button("Watch").clicked() {
+                        watched = Some((addr, None));
+                    }
+
+                    if ui.small_button("Freeze").clicked() {
+                        watched = Some((addr, Some(now)));
+                    }
+                });
+            }
+        });
+
+        if let Some((addr, freeze)) = watched {
+            add_watch(state, Watch { addr, width, label: String::new(), freeze }, sender);
+        }
+
+        ui.separator();
+        watch_list(ui, state, debug, sender);
+    });
+
+    state.open = open;
+}
+
+fn add_watch(state: &mut SearchState, watch: Watch, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    if let Some(value) = watch.freeze {
+        sender.send(EmuMsgIn::Freeze { addr: watch.addr, bytes: watch.bytes(value) }).unwrap();
+    }
+
+    match state.watches.iter_mut().find(|existing| existing.addr == watch.addr) {
+        Some(existing) => *existing = watch,
+        None => state.watches.push(watch),
+    }
+}
+
+fn watch_list(ui: &mut egui::Ui, state: &mut SearchState, debug: &DebugState, sender: &mpsc::UnboundedSender<EmuMsgIn>) {
+    ui.label(RichText::new("Watches").strong());
+
+    if state.watches.is_empty() {
+        ui.weak("Nothing watched yet");
+        return;
+    }
+
+    let memory = debug.emu_state.as_ref().map(|s| s.memory.as_slice()).unwrap_or_default();
+    let mut removed = None;
+
+    egui::Grid::new("search_watches").striped(true).show(ui, |ui| {
+        for (i, watch) in state.watches.iter_mut().enumerate() {
+            ui.add(egui::TextEdit::singleline(&mut watch.label).hint_text("label").desired_width(80.0));
+            ui.monospace(format!("${:04X}", watch.addr));
+
+            let now = search::read(memory, watch.addr, watch.width);
+            ui.monospace(format!("{now} (${now:0w$X})", w = if watch.width == Width::Byte { 2 } else { 4 }));
+
+            let mut frozen = watch.freeze.is_some();
+            if ui.checkbox(&mut frozen, "Freeze").changed() {
+                watch.freeze = frozen.then_some(now);
+
+                match watch.freeze {
+                    Some(value) => sender.send(EmuMsgIn::Freeze { addr: watch.addr, bytes: watch.bytes(value) }).unwrap(),
+                    None => sender.send(EmuMsgIn::Unfreeze(watch.addr)).unwrap(),
+                }
+            }
+
+            if let Some(mut value) = watch.freeze {
+                if ui.add(egui::DragValue::new(&mut value).clamp_range(0..=watch.width.max())).changed() {
+                    watch.freeze = Some(value);
+                    sender.send(EmuMsgIn::Freeze { addr: watch.addr, bytes: watch.bytes(value) }).unwrap();
+                }
+            } else {
+                ui.label("");
+            }
+
+            if ui.small_button("x").clicked() {
+                removed = Some(i);
+            }
+
+            ui.end_row();
+        }
+    });
+
+    if let Some(i) = removed {
+        let watch = state.watches.remove(i);
+
+        if watch.freeze.is_some() {
+            sender.send(EmuMsgIn::Unfreeze(watch.addr)).unwrap();
+        }
+    }
+}
+
+/// Decimal, or hex with a `$` or `0x` prefix
+fn parse_value(text: &str) -> Option<u16> {
+    let text = text.trim();
+
+    match text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
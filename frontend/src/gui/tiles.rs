@@ -0,0 +1,128 @@
+use egui::{load::SizedTexture, pos2, vec2, Color32, ColorImage, Context, Rect, Sense, Stroke, TextureOptions};
+use gamboye_core::vram::{self, Palette};
+
+use crate::state::{DebugState, StateDump};
+
+/// Tiles per row, which puts each 128 tile block on 8 rows of its own
+const COLUMNS: usize = 16;
+
+pub fn show(ctx: &Context, state: &mut DebugState) {
+    let mut open = state.tiles.open;
+
+    egui::Window::new("Tiles").open(&mut open).resizable(false).show(ctx, |ui| {
+        let Some(emu_state) = state.emu_state.as_ref() else {
+            ui.label("No rom loaded");
+            return;
+        };
+
+        let tiles = &mut state.tiles;
+        let cgb = emu_state.is_cgb();
+        // DMG only has BGP, OBP0 and OBP1
+        let palettes = match (cgb, tiles.obj) {
+            (true, _) => 8,
+            (false, false) => 1,
+            (false, true) => 2,
+        };
+
+        ui.horizontal(|ui| {
+            ui.add(egui::Slider::new(&mut tiles.zoom, 1..=4).text("Zoom"));
+            ui.checkbox(&mut tiles.grid, "Grid");
+        });
+
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(cgb, |ui| {
+                ui.radio_value(&mut tiles.bank, 0, "Bank 0");
+                ui.radio_value(&mut tiles.bank, 1, "Bank 1");
+            });
+
+            ui.separator();
+            ui.radio_value(&mut tiles.obj, false, "BG");
+            ui.radio_value(&mut tiles.obj, true, "OBJ");
+
+            tiles.palette = tiles.palette.min(palettes - 1);
+            ui.add_enabled(palettes > 1, egui::DragValue::new(&mut tiles.palette).clamp_range(0..=palettes - 1).prefix("Palette "));
+        });
+
+        if !cgb {
+            tiles.bank = 0;
+        }
+
+        let Some(bank) = emu_state.vram(tiles.bank) else {
+            ui.label("VRAM bank isn't available");
+            return;
+        };
+
+        let palette = palette(emu_state, tiles.obj, tiles.palette);
+        let (size, image) = vram::tile_sheet(bank, COLUMNS, &palette);
+        let texture = ctx.load_texture("tile_viewer", ColorImage::from_rgb(size, &image), TextureOptions::NEAREST);
+
+        let zoom = tiles.zoom as f32;
+        let image_size = vec2(size[0] as f32, size[1] as f32) * zoom;
+        let response = ui.add(egui::Image::from_texture(SizedTexture::new(texture.id(), image_size)).sense(Sense::hover()));
+        let rect = response.rect;
+
+        if tiles.grid {
+            let stroke = Stroke::new(1.0, Color32::from_gray(128).gamma_multiply(0.5));
+            let tile = 8.0 * zoom;
+
+            for col in 1..COLUMNS {
+                let x = rect.left() + col as f32 * tile;
+                ui.painter().vline(x, rect.y_range(), stroke);
+            }
+
+            for row in 1..size[1] / 8 {
+                let y = rect.top() + row as f32 * tile;
+                ui.painter().hline(rect.x_range(), y, stroke);
+            }
+        }
+
+        if let Some(pos) = response.hover_pos() {
+            let col = ((pos.x - rect.left()) / (8.0 * zoom)) as usize;
+            let row = ((pos.y - rect.top()) / (8.0 * zoom)) as usize;
+            let index = row * COLUMNS + col;
+
+            if col < COLUMNS && index < vram::TILES {
+                let tile_rect = Rect::from_min_size(pos2(rect.left() + col as f32 * 8.0 * zoom, rect.top() + row as f32 * 8.0 * zoom), vec2(8.0, 8.0) * zoom);
+                ui.painter().rect_stroke(tile_rect, 0.0, Stroke::new(1.0, Color32::RED));
+
+                response.on_hover_text(hover_text(index, tiles.bank));
+            }
+        }
+
+        tiles.texture = Some(texture);
+    });
+
+    state.tiles.open = open;
+}
+
+/// BG or OBJ palette `index`, as the game currently has it set
+pub fn palette(emu_state: &StateDump, obj: bool, index: usize) -> Palette {
+    match emu_state.palette_ram {
+        Some(ref ram) => {
+            let ram = if obj { ram.get(64..).unwrap_or_default() } else { ram.as_slice() };
+            vram::cgb_palette(ram, index)
+        },
+        None => {
+            let reg = match (obj, index) {
+                (false, _) => 0xFF47,
+                (true, 0) => 0xFF48,
+                (true, _) => 0xFF49,
+            };
+
+            vram::dmg_palette(emu_state.memory.get(reg).copied().unwrap_or(0xE4))
+        },
+    }
+}
+
+fn hover_text(index: usize, bank: usize) -> String {
+    let addr = vram::tile_addr(index);
+
+    // how a tile map or OAM entry would refer to it, which depends on LCDC.4 for the BG
+    let refs = match index {
+        0..=127 => format!("${index:02X} with $8000 addressing"),
+        128..=255 => format!("${index:02X} with either addressing"),
+        _ => format!("${:02X} with $8800 addressing", index - 256),
+    };
+
+    format!("Tile {index} (${index:03X}) in bank {bank}\nAddress ${addr:04X}\n{refs}")
+}
@@ -1,13 +1,13 @@
 #![allow(dead_code)]
 
-use std::process::exit;
+use std::{path::PathBuf, process::exit};
 
 use eframe::egui;
 use egui::{vec2, Vec2};
+use gamboye_core::{comms, pacing, runner, savestate};
 use gui::TopState;
 
-mod comms;
-mod runner;
+mod audio;
 mod gui;
 mod state;
 
@@ -25,13 +25,16 @@ async fn main() -> Result<(), eframe::Error> {
         ..Default::default()
     };
 
-    let Some(filename) = std::env::args().nth(1) else {
-        eprintln!("Usage: {} <rom path>", std::env::current_exe().unwrap().file_name().unwrap().to_str().unwrap());
-        exit(1);
-    };
-    let Ok(rom) = std::fs::read(&filename) else {
-        eprintln!("File not found: {filename}");
-        exit(1);
+    // the rom can also be opened from the file menu, so it's optional here
+    let rom = if let Some(filename) = std::env::args().nth(1) {
+        let Ok(rom) = std::fs::read(&filename) else {
+            eprintln!("File not found: {filename}");
+            exit(1);
+        };
+
+        Some((PathBuf::from(filename), rom))
+    } else {
+        None
     };
 
     eframe::run_native("gamboye", options, Box::new(|cc| Box::new(TopState::new(cc, rom))))
@@ -1,28 +1,39 @@
-use std::{collections::VecDeque, sync::{atomic::AtomicBool, Arc}, time::Instant};
+use std::{collections::VecDeque, path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Instant};
 
 use egui::{mutex::Mutex, vec2, Color32, ColorImage, Mesh, Rect, TextureHandle, TextureOptions};
-use tokio::sync::mpsc;
+use gamboye_core::{breakpoints::BreakpointList, cheats::CheatList, events::{Category, Event}, movie::MovieStatus, search::{Search, Watch, Width}, sink::FrameSink, symbols::Symbols};
+use gbc::{Gbc, Mmu};
+use tokio::{sync::mpsc, task::JoinHandle};
 
-use crate::{comms::{EmuMsgIn, EmuMsgOut}, gui::BASE_DISPLAY_POS, runner::{self, Breakpoints, EmuStatus}};
+pub use gamboye_core::state::StateDump;
 
+use crate::{audio::AudioOutput, comms::{EmuMsgIn, EmuMsgOut}, gui::BASE_DISPLAY_POS, runner::{self, EmuStatus}};
+
+#[derive(Default)]
 pub struct InnerEmuState {
     /// This should always be emu::WIDTH * emu::HEIGHT elements
     // pub fb: Mutex<Vec<Color32>>,
     /// This should always be (emu::WIDTH * emu::HEIGHT * 4) elements
     pub fb: Mutex<Vec<u8>>,
-    pub vram: Mutex<Vec<u8>>,
     pub status: Mutex<EmuStatus>,
     pub fb_pending: AtomicBool,
 }
 
-impl Default for InnerEmuState {
-    fn default() -> Self {
-        Self {
-            fb: Default::default(),
-            vram: Mutex::new(vec![0; 128 * 192 * 3]),
-            status: Default::default(),
-            fb_pending: Default::default(),
-        }
+/// Hands frames from the runner to the UI, and wakes the UI up to draw them
+pub struct EguiSink {
+    pub ctx: egui::Context,
+    pub atoms: Arc<InnerEmuState>,
+}
+
+impl FrameSink for EguiSink {
+    fn present(&mut self, emu: &Gbc<Mmu>) {
+        *self.atoms.fb.lock() = emu.cpu.ppu.fb.clone();
+        self.atoms.fb_pending.store(true, Ordering::Relaxed);
+        self.ctx.request_repaint();
+    }
+
+    fn set_status(&mut self, status: EmuStatus) {
+        *self.atoms.status.lock() = status;
     }
 }
 
@@ -34,10 +45,13 @@ pub struct EmuState {
     pub display_rect: Rect,
     pub display: ColorImage,
     pub texture: TextureHandle,
+    pub rom_path: Option<PathBuf>,
+    /// The running emu's task, which the next one waits on so the .sav is written before it's read
+    pub task: Option<JoinHandle<()>>,
 }
 
 impl EmuState {
-    pub fn new(ctx: &egui::Context, sender: mpsc::UnboundedSender<EmuMsgIn>, receiver: mpsc::UnboundedReceiver<EmuMsgOut>) -> Self {
+    pub fn new(ctx: &egui::Context, sender: Option<mpsc::UnboundedSender<EmuMsgIn>>, receiver: mpsc::UnboundedReceiver<EmuMsgOut>) -> Self {
         let display_rect = Rect::from_min_size(BASE_DISPLAY_POS, vec2(runner::WIDTH as f32, runner::HEIGHT as f32));
         let display = ColorImage::new([runner::WIDTH, runner::HEIGHT], Color32::YELLOW);
         let texture = ctx.load_texture("emu_display", display.clone(), TextureOptions::NEAREST);
@@ -45,12 +59,69 @@ impl EmuState {
 
         Self {
             atoms: Default::default(),
-            sender: Some(sender),
+            sender,
             receiver,
             display_mesh,
             display_rect,
             display,
             texture,
+            rom_path: None,
+            task: None,
+        }
+    }
+}
+
+/// The last message from the runner, shown along the bottom of the window for a while
+#[derive(Clone, Debug, Default)]
+pub struct StatusState {
+    pub text: String,
+    pub error: bool,
+    pub shown: Option<Instant>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FileState {
+    pub error: Option<String>,
+    pub movie: Option<MovieStatus>,
+}
+
+pub struct AudioState {
+    /// None if there's no usable output device, in which case audio goes nowhere
+    pub output: Option<AudioOutput>,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioState {
+    fn default() -> Self {
+        let output = match AudioOutput::new() {
+            Ok(output) => Some(output),
+            Err(err) => {
+                eprintln!("Couldn't open audio output, continuing without sound: {err:?}");
+                None
+            }
+        };
+
+        Self {
+            output,
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpeedState {
+    /// Percent of normal speed
+    pub percent: u32,
+    pub slow_motion: bool,
+}
+
+impl Default for SpeedState {
+    fn default() -> Self {
+        Self {
+            percent: 100,
+            slow_motion: false,
         }
     }
 }
@@ -59,7 +130,6 @@ impl EmuState {
 pub struct PerfState {
     pub open: bool,
     pub last_second: Option<Instant>,
-    pub last_frame: Instant,
     pub fps_history: VecDeque<usize>,
     pub min_fps: usize,
     pub max_fps: usize,
@@ -71,7 +141,6 @@ impl Default for PerfState {
         Self {
             open: false,
             last_second: None,
-            last_frame: Instant::now(),
             fps_history: VecDeque::with_capacity(crate::gui::perf::MAX_FPS_HISTORY),
             min_fps: usize::MAX,
             max_fps: 0,
@@ -84,16 +153,196 @@ impl Default for PerfState {
 pub struct DebugState {
     pub open: bool,
     pub emu_status: EmuStatus,
-    pub vram: Option<TextureHandle>,
     pub emu_state: Option<StateDump>,
     pub stopped: bool,
-    pub breakpoints: Breakpoints,
+    pub breakpoints: BreakpointsState,
+    /// Where the breakpoint list is saved, next to the rom
+    pub breakpoints_path: Option<PathBuf>,
+    /// Address typed into the Run To field
+    pub run_to: String,
+    pub disasm: DisasmState,
+    pub tiles: TilesState,
+    pub maps: MapsState,
+    pub oam: OamState,
+    pub io: IoState,
+    pub events: EventsState,
+    /// Labels from the rom's .sym file
+    pub symbols: Symbols,
+    pub trace: TraceState,
+    /// Byte being edited in the memory view, and what's been typed so far
+    pub mem_edit: Option<(u16, String)>,
 }
 
-#[derive(Clone, Debug)]
-pub struct StateDump {
-    pub next_instruction: gbc::Instruction,
-    pub regs: gbc::Registers,
-    pub io_regs: gbc::IoRegs,
-    pub memory: Vec<u8>,
+/// Cheat codes for the loaded rom, and what's been typed in for a new one
+#[derive(Clone, Default)]
+pub struct CheatsState {
+    pub open: bool,
+    pub list: CheatList,
+    /// Where the list is saved, next to the rom
+    pub path: Option<PathBuf>,
+    pub code: String,
+    pub label: String,
+    pub error: Option<String>,
+}
+
+/// The cheat finder's search in progress, and the addresses picked out of it
+#[derive(Clone, Default)]
+pub struct SearchState {
+    pub open: bool,
+    /// Width for the next search, the running one keeps its own
+    pub width: Width,
+    pub search: Option<Search>,
+    /// Value typed in for the Value filter
+    pub value: String,
+    pub watches: Vec<Watch>,
+}
+
+#[derive(Clone)]
+pub struct TilesState {
+    pub open: bool,
+    /// Screen pixels per tile pixel
+    pub zoom: usize,
+    pub grid: bool,
+    /// VRAM bank, only 1 on CGB
+    pub bank: usize,
+    /// Whether `palette` is an OBJ palette rather than a BG one
+    pub obj: bool,
+    pub palette: usize,
+    pub texture: Option<TextureHandle>,
+}
+
+impl Default for TilesState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            zoom: 2,
+            grid: false,
+            bank: 0,
+            obj: false,
+            palette: 0,
+            texture: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MapsState {
+    pub open: bool,
+    /// Show the window's map rather than the BG's
+    pub window: bool,
+    pub grid: bool,
+    /// Outline what's on screen
+    pub viewport: bool,
+    pub texture: Option<TextureHandle>,
+}
+
+impl Default for MapsState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            window: false,
+            grid: false,
+            viewport: true,
+            texture: None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct OamState {
+    pub open: bool,
+    /// Sprite under the mouse in the list, outlined on the main display
+    pub hovered: Option<usize>,
+    pub texture: Option<TextureHandle>,
+}
+
+#[derive(Clone)]
+pub struct IoState {
+    pub open: bool,
+    /// Registers as of the step before the current state, for highlighting changes
+    pub prev: Vec<u8>,
+    pub decode: bool,
+}
+
+impl Default for IoState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            prev: Vec::new(),
+            decode: true,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct EventsState {
+    pub open: bool,
+    pub logging: bool,
+    /// The last few frames' worth, oldest first
+    pub events: Vec<Event>,
+    /// Frame being looked at, or None to follow the latest
+    pub frame: Option<usize>,
+    pub hidden: Vec<Category>,
+    pub filter: String,
+}
+
+/// Trace options as typed in, blank meaning no filter
+#[derive(Clone, Default)]
+pub struct TraceState {
+    pub path: String,
+    pub frames: String,
+    pub pc: String,
+    pub bank: String,
+    /// Hold LY at $90 like Gameboy Doctor
+    pub pin_ly: bool,
+    pub active: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct DisasmState {
+    pub open: bool,
+    pub follow_pc: bool,
+    /// Address typed into the goto field
+    pub goto: String,
+    /// Scrolled to on the next frame
+    pub scroll_to: Option<u16>,
+    /// Where the last goto or followed jump landed, which always starts a line
+    pub anchor: u16,
+    pub last_pc: Option<u16>,
+    /// Addresses of the jumps that were followed, for going back
+    pub history: Vec<u16>,
+    /// Where each line starts, which takes a sweep of the whole address space so it's only redone when needed
+    pub starts: Vec<u16>,
+    /// The anchor `starts` was swept with, or None once a new state dump has come in
+    pub starts_anchor: Option<u16>,
+}
+
+impl Default for DisasmState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            follow_pc: true,
+            goto: String::new(),
+            scroll_to: None,
+            anchor: 0,
+            last_pc: None,
+            history: Vec::new(),
+            starts: Vec::new(),
+            starts_anchor: None,
+        }
+    }
+}
+
+/// The breakpoint list, and what's been typed in for a new one
+#[derive(Clone, Default)]
+pub struct BreakpointsState {
+    pub list: BreakpointList,
+    /// Index into the kinds in gui::debug
+    pub kind: usize,
+    pub arg: String,
+    pub label: String,
+    /// Only used by conditions
+    pub hit_count: u32,
+    pub error: Option<String>,
 }
\ No newline at end of file
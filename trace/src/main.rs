@@ -0,0 +1,160 @@
+use std::{env::args, fs::read, io::{stdout, BufWriter}, process::exit};
+
+use gamboye_core::{access, apu::{self, Apu}, audio::{AudioSink, Resampler, WavSink}, mbc::Mapper, pacing, trace::{self, TraceFilter, Tracer}};
+use gbc::{CpuError, CpuStatus, Gbc, Mmu};
+
+/// Sample rate of the .wav written by --wav
+const WAV_RATE: u32 = 48000;
+
+const USAGE: &str = "\
+Usage: trace <rom> [options]
+
+Writes a trace of <rom> in Gameboy Doctor's format, one line per instruction.
+
+Options:
+    --out <file>        Write to <file> instead of stdout
+    --frames <range>    Only trace these frames, e.g. 10-20 or 10-
+    --pc <range>        Only trace instructions in this hex range, e.g. 4000-7FFF
+    --bank <n>          Only trace instructions run from this ROM bank
+    --max-frames <n>    Stop after this many frames
+    --doctor            Hold LY at $90 like Gameboy Doctor's reference logs
+    --wav <file>        Also write the game's audio to <file>";
+
+fn main() {
+    let mut args = args().skip(1);
+    let mut rom_path = None;
+    let mut out = None;
+    let mut filter = TraceFilter::default();
+    let mut max_frames = None;
+    let mut wav_path = None;
+    let mut pin_ly = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| fail(&format!("{arg} needs a value")));
+
+        match arg.as_str() {
+            "--out" => out = Some(value()),
+            "--frames" => filter.frames = Some(trace::parse_frames(&value()).unwrap_or_else(|| fail("bad frame range"))),
+            "--pc" => filter.pc = Some(trace::parse_pcs(&value()).unwrap_or_else(|| fail("bad pc range"))),
+            "--bank" => filter.bank = Some(value().parse().unwrap_or_else(|_| fail("bad bank"))),
+            "--wav" => wav_path = Some(value()),
+            "--doctor" => pin_ly = true,
+            "--max-frames" => max_frames = Some(value().parse::<usize>().unwrap_or_else(|_| fail("bad frame count"))),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return;
+            },
+            _ if rom_path.is_none() && !arg.starts_with("--") => rom_path = Some(arg),
+            _ => fail(&format!("unexpected argument {arg}")),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        fail("no rom given");
+    };
+
+    let Ok(rom) = read(&rom_path) else {
+        eprintln!("File not found: {rom_path}");
+        exit(1);
+    };
+
+    let mut tracer = match out {
+        Some(ref path) => Tracer::create(path, filter).unwrap_or_else(|err| {
+            eprintln!("Couldn't create {path}: {err}");
+            exit(1);
+        }),
+        None => Tracer::new(BufWriter::new(stdout()), filter),
+    };
+    tracer.pin_ly = pin_ly;
+
+    let mbc = gbc::get_mbc(&rom);
+    let mut sys = gbc::Gbc::new(mbc, false, true);
+    sys.load_rom(&rom);
+    let mut mapper = Mapper::new(&rom);
+
+    let mut wav = wav_path.map(|path| match WavSink::create(&path, WAV_RATE) {
+        Ok(sink) => Wav::new(&sys, sink),
+        Err(err) => {
+            eprintln!("Couldn't create {path}: {err}");
+            exit(1);
+        },
+    });
+
+    let mut frame = 0;
+
+    while !filter.finished(frame) && max_frames.is_none_or(|max| frame < max) {
+        // a closed pipe, like from `| head`, just means we're done
+        if tracer.trace(&mut sys, frame, mapper.rom_bank()).is_err() {
+            return;
+        }
+
+        let before = (sys.cpu.regs.pc, sys.cpu.regs.sp);
+        let access = access::next(&sys);
+        let (status, draw_ready) = sys.step();
+        let access = access::interrupt(&sys, before).unwrap_or(access);
+
+        for access::Write { addr, value } in access.writes.into_iter().flatten() {
+            mapper.write(addr, value);
+        }
+
+        if let Some(ref mut wav) = wav {
+            wav.step(&sys, access, &status);
+        }
+
+        match status {
+            Ok(CpuStatus::Run(_) | CpuStatus::Break(_, _)) => {},
+            Ok(_) => break,
+            Err(err) => {
+                let _ = tracer.flush();
+                eprintln!("CPU error at frame {frame}: {err:?}");
+                exit(1);
+            },
+        }
+
+        if draw_ready {
+            sys.set_drawn();
+            frame += 1;
+        }
+    }
+
+    let _ = tracer.flush();
+}
+
+/// Plays the sound registers into a .wav, the same way the runner does for its audio sink
+struct Wav {
+    apu: Apu,
+    resampler: Resampler,
+    sink: WavSink,
+    buf: Vec<[f32; 2]>,
+}
+
+impl Wav {
+    fn new(sys: &Gbc<Mmu>, sink: WavSink) -> Self {
+        let mut apu = Apu::default();
+        apu.sync(&sys.cpu.memory);
+
+        Self {
+            apu,
+            resampler: Resampler::new(apu::SAMPLE_RATE, WAV_RATE),
+            sink,
+            buf: Vec::new(),
+        }
+    }
+
+    fn step(&mut self, sys: &Gbc<Mmu>, access: access::Access, status: &Result<CpuStatus, CpuError>) {
+        self.apu.tick(pacing::step_cycles(sys, status));
+
+        for access::Write { addr, value } in access.writes.into_iter().flatten() {
+            self.apu.write(addr, value);
+        }
+
+        self.buf.clear();
+        self.resampler.process(&self.apu.take_samples(), &mut self.buf);
+        self.sink.push(&self.buf);
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{msg}\n\n{USAGE}");
+    exit(2);
+}